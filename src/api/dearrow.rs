@@ -0,0 +1,114 @@
+use isahc::http::StatusCode;
+use isahc::prelude::*;
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::sponsorblock::hash_prefix;
+use super::{get, Error};
+
+lazy_static! {
+    // Video grids ask for the same videos over and over, and every response also
+    // contains other videos sharing the hash prefix
+    static ref CACHE: Mutex<HashMap<String, Branding>> = Mutex::new(HashMap::new());
+}
+
+/// Community submitted replacements for a video's title and thumbnail
+#[derive(Debug, Clone, Default)]
+pub struct Branding {
+    pub title: Option<String>,
+    // Seconds into the video to take the thumbnail from
+    pub thumbnail_time: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BrandingResponse {
+    titles: Vec<TitleResponse>,
+    thumbnails: Vec<ThumbnailResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TitleResponse {
+    title: String,
+    original: bool,
+    votes: i64,
+    locked: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ThumbnailResponse {
+    timestamp: Option<f64>,
+    original: bool,
+    votes: i64,
+    locked: bool,
+}
+
+impl From<BrandingResponse> for Branding {
+    fn from(response: BrandingResponse) -> Self {
+        // Submissions are sorted by score, ones with negative votes haven't been accepted yet
+        Branding {
+            title: response
+                .titles
+                .into_iter()
+                .find(|x| x.votes >= 0 || x.locked)
+                .filter(|x| !x.original)
+                .map(|x| clean_title(&x.title)),
+            thumbnail_time: response
+                .thumbnails
+                .into_iter()
+                .find(|x| x.votes >= 0 || x.locked)
+                .filter(|x| !x.original)
+                .and_then(|x| x.timestamp),
+        }
+    }
+}
+
+/// DeArrow marks words that shouldn't be recased with a `>`
+fn clean_title(title: &str) -> String {
+    title
+        .split(' ')
+        .map(|x| x.strip_prefix('>').unwrap_or(x))
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+pub async fn branding(server: &str, video_id: &str) -> Result<Branding, Error> {
+    let server = server.trim_end_matches('/');
+    let key = |id: &str| format!("{}/{}", server, id);
+    if let Some(branding) = CACHE.lock()?.get(&key(video_id)) {
+        return Ok(branding.clone());
+    }
+
+    let mut response = get(&format!(
+        "{}/api/branding/{}",
+        server,
+        hash_prefix(video_id)
+    ))
+    .await?;
+    let videos: HashMap<String, BrandingResponse> = match response.status() {
+        StatusCode::OK => response.json().await?,
+        // No video with this prefix has any submissions
+        StatusCode::NOT_FOUND => HashMap::new(),
+        _ => return Err(Error::BadStatusCode),
+    };
+
+    let mut cache = CACHE.lock()?;
+    for (id, branding) in videos {
+        cache.insert(key(&id), branding.into());
+    }
+    Ok(cache
+        .entry(key(video_id))
+        .or_insert_with(Branding::default)
+        .clone())
+}
+
+/// Thumbnail rendered by the thumbnail server. Its URI holds the whole video ID
+pub fn thumbnail_uri(thumbnail_server: &str, video_id: &str, time: f64) -> String {
+    format!(
+        "{}/api/v1/getThumbnail?videoID={}&time={}",
+        thumbnail_server.trim_end_matches('/'),
+        urlencoding::encode(video_id),
+        time
+    )
+}
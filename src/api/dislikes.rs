@@ -0,0 +1,57 @@
+use isahc::http::StatusCode;
+use isahc::prelude::*;
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::{get, Error};
+
+// Vote counts barely change while watching, so avoid asking again every time a video is opened
+const CACHE_DURATION: Duration = Duration::from_secs(10 * 60);
+
+lazy_static! {
+    static ref CACHE: Mutex<HashMap<String, (Instant, Votes)>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Votes {
+    pub likes: u64,
+    pub dislikes: u64,
+}
+
+impl Votes {
+    /// Fraction of votes that are likes
+    pub fn ratio(&self) -> f64 {
+        let total = self.likes + self.dislikes;
+        if total == 0 {
+            0.0
+        } else {
+            self.likes as f64 / total as f64
+        }
+    }
+}
+
+/// Fetches estimated vote counts from a Return YouTube Dislike server
+pub async fn votes(server: &str, video_id: &str) -> Result<Votes, Error> {
+    let uri = format!(
+        "{}/votes?videoId={}",
+        server.trim_end_matches('/'),
+        urlencoding::encode(video_id)
+    );
+    if let Some((fetched, votes)) = CACHE.lock()?.get(&uri) {
+        if fetched.elapsed() < CACHE_DURATION {
+            return Ok(votes.clone());
+        }
+    }
+
+    let mut response = get(&uri).await?;
+    if response.status() == StatusCode::OK {
+        let votes: Votes = response.json().await?;
+        CACHE.lock()?.insert(uri, (Instant::now(), votes.clone()));
+        Ok(votes)
+    } else {
+        Err(Error::BadStatusCode)
+    }
+}
@@ -1,17 +1,35 @@
+pub mod dearrow;
+pub mod dislikes;
+mod network;
+pub mod sponsorblock;
+pub mod trending;
+
 use enum_dispatch::enum_dispatch;
-use futures::future::join_all;
+use futures::future::{self, join_all, Either};
+use futures::stream::{self, Stream, StreamExt};
+use gtk::glib;
+use isahc::http::StatusCode;
 use isahc::prelude::*;
-use isahc::{config::RedirectPolicy, http::StatusCode, HttpClient};
 use lazy_static::lazy_static;
 use rand::{self, seq::SliceRandom};
 use regex::Regex;
 use serde::{self, Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::VecDeque;
+use std::future::Future;
 use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, PoisonError, RwLock};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
+use trending::TrendingCategory;
+
+pub use network::{
+    block_requests, check_tor, clear_contacted_hosts, contacted_hosts, get, media_route,
+    record_host, requests_blocked, set_proxy, MediaRoute,
+};
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Debug, Error)]
 pub enum Error {
@@ -33,6 +51,22 @@ pub enum Error {
     InstanceNotFound,
     #[error("Instance returned bad status code")]
     BadStatusCode,
+    #[error("Instance took too long to respond")]
+    Timeout,
+    #[error("Invalid proxy address")]
+    InvalidProxy,
+    #[error("The proxy isn't set up correctly, so nothing is sent")]
+    ProxyUnavailable,
+}
+
+impl Error {
+    /// Whether the instance never answered, which says nothing about the instance itself
+    pub fn is_connection_error(&self) -> bool {
+        matches!(
+            self,
+            Error::RequestError(_) | Error::IoError(_) | Error::Timeout | Error::ProxyUnavailable
+        )
+    }
 }
 
 impl<T> From<PoisonError<T>> for Error {
@@ -59,6 +93,25 @@ pub struct InstanceResponse {
     pub uri: String,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedUrl {
+    pub ucid: Option<String>,
+    pub video_id: Option<String>,
+    pub playlist_id: Option<String>,
+    pub start_time_seconds: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HashtagPage {
+    results: Vec<Video>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchSuggestions {
+    suggestions: Vec<String>,
+}
+
 // Utility
 
 pub type Instances = Vec<Arc<Instance>>;
@@ -74,6 +127,126 @@ pub struct InstanceInfo {
     pub has_trending: Option<bool>,
     pub has_popular: Option<bool>,
     pub open_registrations: bool,
+    #[serde(default)]
+    pub health: InstanceHealth,
+}
+
+// Discovery
+const DISCOVERY_CONCURRENCY: usize = 16;
+const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Health
+const MAX_LATENCY_SAMPLES: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endpoint {
+    Search,
+    Videos,
+    Trending,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EndpointStats {
+    pub successes: u32,
+    pub failures: u32,
+}
+
+impl EndpointStats {
+    pub fn total(&self) -> u32 {
+        self.successes + self.failures
+    }
+
+    pub fn success_rate(&self) -> Option<f64> {
+        if self.total() == 0 {
+            None
+        } else {
+            Some(self.successes as f64 / self.total() as f64)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InstanceHealth {
+    // Most recent sample is at the back
+    pub latencies: VecDeque<u32>,
+    pub search: EndpointStats,
+    pub videos: EndpointStats,
+    pub trending: EndpointStats,
+    pub api_blocked: bool,
+    // Seconds since the unix epoch
+    pub last_checked: Option<u64>,
+}
+
+impl InstanceHealth {
+    pub fn endpoint(&self, endpoint: Endpoint) -> &EndpointStats {
+        match endpoint {
+            Endpoint::Search => &self.search,
+            Endpoint::Videos => &self.videos,
+            Endpoint::Trending => &self.trending,
+        }
+    }
+
+    fn endpoint_mut(&mut self, endpoint: Endpoint) -> &mut EndpointStats {
+        match endpoint {
+            Endpoint::Search => &mut self.search,
+            Endpoint::Videos => &mut self.videos,
+            Endpoint::Trending => &mut self.trending,
+        }
+    }
+
+    pub fn record(&mut self, endpoint: Endpoint, latency: Option<Duration>) {
+        let stats = self.endpoint_mut(endpoint);
+        if latency.is_some() {
+            stats.successes = stats.successes.saturating_add(1);
+        } else {
+            stats.failures = stats.failures.saturating_add(1);
+        }
+        if let Some(latency) = latency {
+            if self.latencies.len() >= MAX_LATENCY_SAMPLES {
+                self.latencies.pop_front();
+            }
+            self.latencies
+                .push_back(latency.as_millis().min(u32::MAX as u128) as u32);
+        }
+    }
+
+    /// Returns the latency (in milliseconds) below which `percentile`% of samples fall
+    pub fn latency_percentile(&self, percentile: u8) -> Option<u32> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<u32> = self.latencies.iter().copied().collect();
+        sorted.sort_unstable();
+        let index = (sorted.len() - 1) * percentile.min(100) as usize / 100;
+        Some(sorted[index])
+    }
+
+    pub fn success_rate(&self) -> Option<f64> {
+        let successes = self.search.successes + self.videos.successes + self.trending.successes;
+        let total = self.search.total() + self.videos.total() + self.trending.total();
+        if total == 0 {
+            None
+        } else {
+            Some(successes as f64 / total as f64)
+        }
+    }
+
+    /// Weight used when randomly picking an instance. Higher is better.
+    pub fn score(&self) -> f64 {
+        if self.api_blocked {
+            return 0.0;
+        }
+        // Instances we know nothing about are given the benefit of the doubt
+        let reliability = self.success_rate().unwrap_or(0.75);
+        let speed = match self.latency_percentile(90) {
+            Some(latency) => 1.0 / (1.0 + latency as f64 / 1000.0),
+            None => 0.5,
+        };
+        // Keep a small floor so that every working instance still gets picked occasionally
+        (0.7 * reliability + 0.3 * speed).max(0.01)
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -105,6 +278,8 @@ pub struct Video {
     pub author_id: String,
     #[serde(rename = "publishedText")]
     pub published: String,
+    #[serde(rename = "liveNow", default)]
+    pub live: bool,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -178,13 +353,132 @@ pub struct DetailedVideo {
     // Media
     #[serde(rename = "formatStreams")]
     pub format_streams: Vec<FormatStream>,
+    #[serde(rename = "adaptiveFormats", default)]
+    pub adaptive_formats: Vec<AdaptiveFormat>,
     pub captions: Vec<Caption>,
+    #[serde(default)]
+    pub storyboards: Vec<StoryboardInfo>,
+    #[serde(rename = "hlsUrl", default)]
+    pub hls_uri: Option<String>,
+
+    // Live streams and premieres
+    #[serde(rename = "liveNow", default)]
+    pub live: bool,
+    #[serde(rename = "isUpcoming", default)]
+    pub upcoming: bool,
+    #[serde(rename = "premiereTimestamp", default)]
+    pub premiere_timestamp: Option<u64>,
 
     // Recommended
     #[serde(rename = "recommendedVideos")]
     pub recommended: Vec<Video>,
 }
 
+impl DetailedVideo {
+    pub fn chapters(&self) -> Vec<Chapter> {
+        parse_chapters(&self.description)
+    }
+
+    /// Live streams only have an HLS manifest, everything else is played from a muxed stream
+    pub fn stream_uri(&self) -> Option<&str> {
+        let format_stream = self.format_streams.last().map(|x| x.uri.as_str());
+        let hls = self.hls_uri.as_deref();
+        if self.live {
+            hls.or(format_stream)
+        } else {
+            format_stream.or(hls)
+        }
+    }
+
+    /// The highest bitrate audio-only format, for listening without downloading video
+    pub fn audio_uri(&self) -> Option<&str> {
+        self.adaptive_formats
+            .iter()
+            .filter(|x| x.is_audio())
+            .max_by_key(|x| x.bitrate())
+            .map(|x| x.uri.as_str())
+            .or_else(|| self.stream_uri())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    // Seconds
+    pub start: u32,
+    pub title: String,
+}
+
+/// Parses "1:02:03" or "02:03" into seconds
+pub fn parse_timestamp(text: &str) -> Option<u32> {
+    let mut seconds = 0;
+    let parts: Vec<&str> = text.split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+    for (index, part) in parts.iter().enumerate() {
+        let value = part.parse::<u32>().ok()?;
+        // Only the first part may go past 59
+        if index > 0 && value >= 60 {
+            return None;
+        }
+        seconds = seconds * 60 + value;
+    }
+    Some(seconds)
+}
+
+/// A chapter from a timestamp and the rest of its line. Titles of only digits are left over
+/// from a lone timestamp, like the "0" of "00:00"
+fn chapter_from(timestamp: &str, title: &str) -> Option<Chapter> {
+    let title = title.trim();
+    if title.is_empty() || title.chars().all(|x| x.is_ascii_digit() || x == ':') {
+        return None;
+    }
+    Some(Chapter {
+        start: parse_timestamp(timestamp)?,
+        title: title.to_string(),
+    })
+}
+
+/// Finds a chapter list (e.g. "00:00 Intro") in a description, following the
+/// same rules as YouTube: it must start at 0:00 and have at least 3 chapters
+fn parse_chapters(description: &str) -> Vec<Chapter> {
+    lazy_static! {
+        // Timestamp first: "00:00 - Intro", "(0:00) Intro", "• 00:00 Intro"
+        static ref LEADING: Regex = Regex::new(
+            r"^[\s•*▶►-]*\(?((?:\d{1,2}:)?\d{1,2}:\d{2})\)?\s*[-–—:|]?\s*(.+?)\s*$"
+        )
+        .unwrap();
+        // Timestamp last: "Intro - 00:00"
+        static ref TRAILING: Regex = Regex::new(
+            r"^\s*(.+?)\s*[-–—:|]?\s*\(?((?:\d{1,2}:)?\d{1,2}:\d{2})\)?\s*$"
+        )
+        .unwrap();
+    }
+
+    let mut chapters: Vec<Chapter> = Vec::new();
+    for line in description.lines() {
+        let chapter = if let Some(captures) = LEADING.captures(line) {
+            chapter_from(&captures[1], &captures[2])
+        } else if let Some(captures) = TRAILING.captures(line) {
+            chapter_from(&captures[2], &captures[1])
+        } else {
+            None
+        };
+        if let Some(chapter) = chapter {
+            // Timestamps that go backwards are usually references, not chapters
+            if chapters.last().map_or(true, |x| chapter.start > x.start) {
+                chapters.push(chapter);
+            }
+        }
+    }
+
+    if chapters.len() >= 3 && chapters[0].start == 0 {
+        chapters
+    } else {
+        Vec::new()
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct FormatStream {
     #[serde(rename = "url")]
@@ -195,6 +489,93 @@ pub struct FormatStream {
     pub size: String,
 }
 
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct AdaptiveFormat {
+    #[serde(rename = "url")]
+    pub uri: String,
+    pub itag: String,
+    #[serde(rename = "type")]
+    pub mime_type: String,
+    pub bitrate: String,
+    pub container: Option<String>,
+    pub encoding: Option<String>,
+    #[serde(rename = "qualityLabel")]
+    pub quality_label: Option<String>,
+    pub resolution: Option<String>,
+    #[serde(rename = "audioQuality")]
+    pub audio_quality: Option<String>,
+}
+
+impl AdaptiveFormat {
+    pub fn is_audio(&self) -> bool {
+        self.mime_type.starts_with("audio/")
+    }
+
+    pub fn bitrate(&self) -> u64 {
+        self.bitrate.parse().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct StoryboardInfo {
+    #[serde(rename = "url")]
+    pub uri: String,
+    #[serde(rename = "templateUrl")]
+    pub template_uri: String,
+    pub width: u32,
+    pub height: u32,
+    pub count: u32,
+    pub interval: u32,
+    #[serde(rename = "storyboardWidth")]
+    pub columns: u32,
+    #[serde(rename = "storyboardHeight")]
+    pub rows: u32,
+    #[serde(rename = "storyboardCount")]
+    pub storyboard_count: u32,
+}
+
+/// One preview image, cut out of a larger sprite sheet
+#[derive(Debug, Clone)]
+pub struct StoryboardTile {
+    // Seconds
+    pub start: f64,
+    pub end: f64,
+    pub sheet_uri: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+fn parse_vtt_timestamp(timestamp: &str) -> Option<f64> {
+    timestamp.split(':').try_fold(0.0, |total, part| {
+        Some(total * 60.0 + part.parse::<f64>().ok()?)
+    })
+}
+
+/// Storyboards are served as WebVTT, with each cue pointing at a region of a sprite sheet
+fn parse_storyboard(vtt: &str) -> Vec<StoryboardTile> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r"(?m)^(\S+) --> (\S+)[^\n]*\n(\S+)#xywh=(\d+),(\d+),(\d+),(\d+)").unwrap();
+    }
+    RE.captures_iter(vtt)
+        .filter_map(|captures| {
+            Some(StoryboardTile {
+                start: parse_vtt_timestamp(&captures[1])?,
+                end: parse_vtt_timestamp(&captures[2])?,
+                sheet_uri: captures[3].to_string(),
+                x: captures[4].parse().ok()?,
+                y: captures[5].parse().ok()?,
+                width: captures[6].parse().ok()?,
+                height: captures[7].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Caption {
     pub label: String,
@@ -203,7 +584,9 @@ pub struct Caption {
     pub uri: String,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+// Invidious leaves out whatever YouTube didn't send, like banners of channels without one
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(default)]
 pub struct DetailedChannel {
     #[serde(rename = "author")]
     pub title: String,
@@ -222,12 +605,27 @@ pub struct DetailedChannel {
     pub description_html: String,
     #[serde(rename = "authorVerified")]
     pub verified: bool,
+    // Seconds since the Unix epoch, 0 if unknown
+    pub joined: i64,
+    pub tags: Vec<String>,
     #[serde(rename = "latestVideos")]
     pub videos: Vec<Video>,
     #[serde(rename = "relatedChannels")]
     pub related_channels: Vec<Channel>,
 }
 
+impl DetailedChannel {
+    /// Banner that is closest to `width` pixels wide
+    pub fn banner(&self, width: u32) -> Option<&Thumbnail> {
+        closest_thumbnail(&self.banners, width)
+    }
+
+    /// Avatar that is closest to `size` pixels wide
+    pub fn thumbnail(&self, size: u32) -> Option<&Thumbnail> {
+        closest_thumbnail(&self.thumbnails, size)
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct DetailedPlaylist {
     pub title: String,
@@ -243,28 +641,62 @@ pub struct DetailedPlaylist {
     pub videos: Vec<Video>,
 }
 
+/// Playlist YouTube generates from a video, its ID starts with "RD"
+#[derive(Debug, Deserialize, Clone)]
+pub struct Mix {
+    pub title: String,
+    #[serde(rename = "mixId")]
+    pub id: String,
+    pub videos: Vec<Video>,
+}
+
+impl Mix {
+    pub fn is_mix_id(id: &str) -> bool {
+        id.starts_with("RD")
+    }
+
+    /// ID of the mix YouTube builds around a video
+    pub fn id_for_video(video_id: &str) -> String {
+        format!("RD{}", video_id)
+    }
+}
+
+impl From<Mix> for DetailedPlaylist {
+    fn from(mix: Mix) -> Self {
+        let thumbnail = mix
+            .videos
+            .first()
+            .and_then(|x| x.thumbnails.first())
+            .map(|x| x.uri.clone())
+            .unwrap_or_default();
+        DetailedPlaylist {
+            title: mix.title,
+            id: mix.id,
+            author: "YouTube".into(),
+            author_id: String::new(),
+            video_count: mix.videos.len() as u64,
+            thumbnail,
+            videos: mix.videos,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct InvidiousClient {
     instances: RwLock<Instances>,
     selected: RwLock<Option<Arc<Instance>>>,
 }
 
-// Global
-lazy_static! {
-    static ref HTTP_CLIENT: HttpClient = HttpClient::builder()
-        .timeout(Duration::from_secs(10))
-        .redirect_policy(RedirectPolicy::Limit(10))
-        .build()
-        .unwrap();
-}
-
 // Functions
-pub async fn fetch_instances() -> Result<Instances, Error> {
-    let response: Vec<(String, InstanceResponse)> = HTTP_CLIENT
-        .get_async("https://api.invidious.io/instances.json?pretty=1&sort_by=type,users")
-        .await?
-        .json()
-        .await?;
+
+/// Fetches the public instance list without contacting any of the instances.
+/// Onion and I2P instances are only included if `include_hidden` is set.
+pub async fn fetch_instances(include_hidden: bool) -> Result<Instances, Error> {
+    let response: Vec<(String, InstanceResponse)> =
+        get("https://api.invidious.io/instances.json?pretty=1&sort_by=type,users")
+            .await?
+            .json()
+            .await?;
 
     let instances: Instances = response
         .into_iter()
@@ -275,14 +707,19 @@ pub async fn fetch_instances() -> Result<Instances, Error> {
                 false
             };
 
-            // TODO: Support more protocols in the future
-            if instance.protocol == "https" {
+            let supported = match instance.protocol.as_str() {
+                "https" => true,
+                "onion" | "i2p" => include_hidden,
+                _ => false,
+            };
+            if supported {
                 Some(Arc::new(Instance {
-                    uri: instance.uri,
+                    uri: instance.uri.trim_end_matches('/').to_string(),
                     info: Arc::new(RwLock::new(InstanceInfo {
                         open_registrations,
                         has_trending: None,
                         has_popular: None,
+                        health: InstanceHealth::default(),
                     })),
                 }))
             } else {
@@ -291,15 +728,40 @@ pub async fn fetch_instances() -> Result<Instances, Error> {
         })
         .collect();
 
-    // Ping in batches of 8 at a time
-    // TODO: find a way to improve this maybe
-    for instances in instances.chunks(8) {
-        join_all(instances.iter().map(|x| x.update_info())).await;
-    }
-
     Ok(instances)
 }
 
+/// Updates the info of every instance, at most `DISCOVERY_CONCURRENCY` at a time.
+/// Results are yielded as soon as each instance responds or times out.
+pub fn probe_instances(
+    instances: Instances,
+) -> impl Stream<Item = (Arc<Instance>, Result<(), Error>)> {
+    stream::iter(instances)
+        .map(|instance| async move {
+            let result = match future::select(
+                Box::pin(instance.update_info()),
+                glib::timeout_future(PROBE_TIMEOUT),
+            )
+            .await
+            {
+                Either::Left((result, _)) => result,
+                Either::Right(_) => Err(Error::Timeout),
+            };
+            (instance, result)
+        })
+        .buffer_unordered(DISCOVERY_CONCURRENCY)
+}
+
+fn choose_by_score(instances: &[Arc<Instance>]) -> Option<Arc<Instance>> {
+    let mut rng = rand::thread_rng();
+    instances
+        .choose_weighted(&mut rng, |x| x.score())
+        .ok()
+        // Every instance is blocked, fall back to picking one at random
+        .or_else(|| instances.choose(&mut rng))
+        .cloned()
+}
+
 fn format_input_uri(uri: &str) -> String {
     lazy_static! {
         static ref RE: Regex = Regex::new(r"^([a-z0-9]+):\/\/").unwrap();
@@ -307,18 +769,73 @@ fn format_input_uri(uri: &str) -> String {
     let uri = uri.trim().trim_end_matches('/');
     if RE.is_match_at(uri, 0) {
         uri.into()
+    } else if uri.ends_with(".onion") || uri.ends_with(".i2p") {
+        // Hidden services are already encrypted end-to-end and rarely have certificates
+        format!("http://{uri}")
     } else {
         format!("https://{uri}")
     }
 }
 
+// Whether media should be fetched through the instance instead of Google's servers
+static PROXY_MEDIA: AtomicBool = AtomicBool::new(false);
+
+pub fn set_proxy_media(enabled: bool) {
+    PROXY_MEDIA.store(enabled, Ordering::Relaxed);
+}
+
+pub fn proxy_media() -> bool {
+    PROXY_MEDIA.load(Ordering::Relaxed)
+}
+
+fn closest_thumbnail(thumbnails: &[Thumbnail], width: u32) -> Option<&Thumbnail> {
+    thumbnails.iter().min_by_key(|x| x.width.abs_diff(width))
+}
+
 fn correct_uri(instance_uri: &str, uri: &str) -> String {
-    if uri.starts_with("/vi/") {
-        // If domain isn't present (i.e. /vi/lcIObyvI3uw/maxres.jpg)
-        format!("{}{}", instance_uri, uri)
-    } else if uri.starts_with("//") {
+    let uri = if uri.starts_with("//") {
         // If protocol isn't present (i.e. //yt3.googleusercontent.com/ytc/...)
         format!("https:{}", uri)
+    } else if uri.starts_with('/') {
+        // If domain isn't present (i.e. /vi/lcIObyvI3uw/maxres.jpg)
+        format!("{}{}", instance_uri, uri)
+    } else {
+        uri.to_string()
+    };
+    if proxy_media() {
+        proxy_uri(instance_uri, &uri)
+    } else {
+        uri
+    }
+}
+
+/// Rewrites a link to one of Google's media servers so that it goes through the instance
+fn proxy_uri(instance_uri: &str, uri: &str) -> String {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r"^https?://([a-z0-9.-]+)(?::\d+)?(/[^?#]*)(\?[^#]*)?").unwrap();
+    }
+    let Some(captures) = RE.captures(uri) else {
+        return uri.to_string();
+    };
+    let host = &captures[1];
+    let path = &captures[2];
+    let query = captures.get(3).map(|x| x.as_str()).unwrap_or_default();
+
+    if host.ends_with(".googlevideo.com") {
+        let separator = if query.is_empty() { "?" } else { "&" };
+        format!("{instance_uri}{path}{query}{separator}host={host}")
+    } else if host.ends_with(".ytimg.com") && path.starts_with("/sb/") {
+        // i.e. https://i9.ytimg.com/sb/<id>/... becomes /sb/i9/<id>/...
+        let authority = host.trim_end_matches(".ytimg.com");
+        format!(
+            "{instance_uri}/sb/{authority}/{}{query}",
+            path.trim_start_matches("/sb/")
+        )
+    } else if host.ends_with(".ytimg.com") {
+        format!("{instance_uri}{path}{query}")
+    } else if host.ends_with(".ggpht.com") || host.ends_with(".googleusercontent.com") {
+        format!("{instance_uri}/ggpht{path}{query}")
     } else {
         uri.to_string()
     }
@@ -368,6 +885,30 @@ impl CorrectUri for DetailedVideo {
     fn thumbnails(&mut self) -> Option<&mut [Thumbnail]> {
         Some(self.thumbnails.as_mut_slice())
     }
+    fn videos(&mut self) -> Option<&mut [Video]> {
+        Some(self.recommended.as_mut_slice())
+    }
+    fn correct_uri_custom(&mut self, instance_uri: &str) {
+        self.author_thumbnails
+            .iter_mut()
+            .for_each(|x| x.uri = correct_uri(instance_uri, &x.uri));
+        self.captions
+            .iter_mut()
+            .for_each(|x| x.uri = correct_uri(instance_uri, &x.uri));
+        self.storyboards.iter_mut().for_each(|x| {
+            x.uri = correct_uri(instance_uri, &x.uri);
+            x.template_uri = correct_uri(instance_uri, &x.template_uri);
+        });
+        self.format_streams
+            .iter_mut()
+            .for_each(|x| x.uri = correct_uri(instance_uri, &x.uri));
+        self.adaptive_formats
+            .iter_mut()
+            .for_each(|x| x.uri = correct_uri(instance_uri, &x.uri));
+        if let Some(uri) = self.hls_uri.as_mut() {
+            *uri = correct_uri(instance_uri, uri);
+        }
+    }
 }
 
 impl CorrectUri for Channel {
@@ -400,6 +941,11 @@ impl CorrectUri for DetailedChannel {
     fn thumbnails(&mut self) -> Option<&mut [Thumbnail]> {
         Some(self.thumbnails.as_mut_slice())
     }
+    fn correct_uri_custom(&mut self, instance_uri: &str) {
+        self.banners
+            .iter_mut()
+            .for_each(|x| x.uri = correct_uri(instance_uri, &x.uri));
+    }
     fn channels(&mut self) -> Option<&mut [Channel]> {
         Some(self.related_channels.as_mut_slice())
     }
@@ -423,20 +969,23 @@ impl CorrectUri for DetailedPlaylist {
     }
 }
 
+impl CorrectUri for Mix {
+    fn videos(&mut self) -> Option<&mut [Video]> {
+        Some(self.videos.as_mut_slice())
+    }
+}
+
 impl Instance {
     pub async fn from_uri(uri: &str) -> Result<Instance, Error> {
         let uri = format_input_uri(uri);
-        let response: StatsResponse = HTTP_CLIENT
-            .get_async(format!("{}/api/v1/stats", &uri))
-            .await?
-            .json()
-            .await?;
+        let response: StatsResponse = get(format!("{}/api/v1/stats", &uri)).await?.json().await?;
         let instance = Instance {
             uri,
             info: Arc::new(RwLock::new(InstanceInfo {
                 has_trending: None,
                 has_popular: None,
                 open_registrations: response.open_registrations,
+                health: InstanceHealth::default(),
             })),
         };
         instance.update_info().await?;
@@ -447,21 +996,37 @@ impl Instance {
         // Some instances don't allow api access (e.g. yewtu.be)
         // Try to access a video and return an error if it fails
         // (and pray that Rick never takes down his magnum opus)
-        let response = HTTP_CLIENT
-            .get_async(format!("{}/api/v1/videos/dQw4w9WgXcQ", self.uri))
-            .await?;
-        if !response.status().is_success() {
-            return Err(Error::BadStatusCode);
+        let result = self
+            .timed(Endpoint::Videos, async {
+                let mut response = get(format!("{}/api/v1/videos/dQw4w9WgXcQ", self.uri)).await?;
+                if !response.status().is_success() {
+                    return Err(Error::BadStatusCode);
+                }
+                // Blocked instances tend to answer with an HTML page instead of JSON
+                response.json::<Value>().await?;
+                Ok(())
+            })
+            .await;
+        match result {
+            Ok(()) => self.info.write()?.health.api_blocked = false,
+            // Only an answer that isn't the video means the API is blocked
+            Err(Error::BadStatusCode | Error::DeserializeError(_)) => {
+                self.info.write()?.health.api_blocked = true;
+                return Err(Error::BadStatusCode);
+            }
+            Err(error) => return Err(error),
         }
-        let mut response = HTTP_CLIENT
-            .get_async(format!("{}/api/v1/popular", self.uri))
-            .await?;
+
+        let mut response = get(format!("{}/api/v1/popular", self.uri)).await?;
         let has_popular = response.json::<Vec<Value>>().await.is_ok();
 
-        let mut response = HTTP_CLIENT
-            .get_async(format!("{}/api/v1/trending", self.uri))
-            .await?;
-        let has_trending = response.json::<Vec<Value>>().await.is_ok();
+        let has_trending = self
+            .timed(Endpoint::Trending, async {
+                let mut response = get(format!("{}/api/v1/trending", self.uri)).await?;
+                Ok(response.json::<Vec<Value>>().await?)
+            })
+            .await
+            .is_ok();
 
         let mut info = self.info.write()?;
         info.has_popular = Some(has_popular);
@@ -470,11 +1035,57 @@ impl Instance {
         Ok(())
     }
 
+    /// Probes every tracked endpoint and records the results in the instance's health
+    pub async fn check_health(&self) -> Result<(), Error> {
+        let result = self.update_info().await;
+        if result.is_ok() {
+            let _ = self
+                .timed(Endpoint::Search, async {
+                    let mut response = get(format!("{}/api/v1/search?q=music", self.uri)).await?;
+                    Ok(response.json::<Vec<Value>>().await?)
+                })
+                .await;
+        }
+        // An instance that couldn't be reached is checked again next time
+        if result.as_ref().is_err_and(Error::is_connection_error) {
+            return result;
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .ok();
+        self.info.write()?.health.last_checked = now;
+        result
+    }
+
+    pub fn score(&self) -> f64 {
+        self.info
+            .read()
+            .map(|info| info.health.score())
+            .unwrap_or_default()
+    }
+
+    async fn timed<T>(
+        &self,
+        endpoint: Endpoint,
+        future: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        let start = Instant::now();
+        let result = future.await;
+        // Blocked requests never reached the instance
+        if matches!(result, Err(Error::ProxyUnavailable)) {
+            return result;
+        }
+        if let Ok(mut info) = self.info.write() {
+            info.health
+                .record(endpoint, result.as_ref().ok().map(|_| start.elapsed()));
+        }
+        result
+    }
+
     pub async fn ping(&self, endpoint: Option<&str>) -> Result<u128, Error> {
         let elapsed = Instant::now();
-        let response = HTTP_CLIENT
-            .get_async(format!("{}{}", self.uri, endpoint.unwrap_or("/")))
-            .await?;
+        let response = get(format!("{}{}", self.uri, endpoint.unwrap_or("/"))).await?;
         if response.status() == StatusCode::OK {
             let elapsed = elapsed.elapsed();
             Ok(elapsed.as_millis())
@@ -486,9 +1097,7 @@ impl Instance {
 
     // Data Requests
     pub async fn stats(&self) -> Result<StatsResponse, Error> {
-        let mut response = HTTP_CLIENT
-            .get_async(&format!("{}/api/v1/stats", self.uri))
-            .await?;
+        let mut response = get(&format!("{}/api/v1/stats", self.uri)).await?;
         if response.status() == StatusCode::OK {
             Ok(response.json::<StatsResponse>().await?)
         } else {
@@ -497,9 +1106,7 @@ impl Instance {
     }
 
     async fn fetch_video_page(&self, endpoint: &str) -> Result<Vec<Content>, Error> {
-        let mut response = HTTP_CLIENT
-            .get_async(&format!("{}{}", self.uri, endpoint))
-            .await?;
+        let mut response = get(&format!("{}{}", self.uri, endpoint)).await?;
 
         if response.status() == StatusCode::OK {
             let mut data: Vec<Content> = response
@@ -522,36 +1129,112 @@ impl Instance {
         self.fetch_video_page("/api/v1/popular").await
     }
 
-    pub async fn trending(&self) -> Result<Vec<Content>, Error> {
-        self.fetch_video_page("/api/v1/trending").await
+    /// `region` is a country code, the instance picks one if it's `None`
+    pub async fn trending(
+        &self,
+        category: TrendingCategory,
+        region: Option<&str>,
+    ) -> Result<Vec<Content>, Error> {
+        let mut params = Vec::new();
+        if category != TrendingCategory::Default {
+            params.push(format!("type={}", category.id()));
+        }
+        if let Some(region) = region {
+            params.push(format!("region={}", urlencoding::encode(region)));
+        }
+        let endpoint = if params.is_empty() {
+            "/api/v1/trending".to_string()
+        } else {
+            format!("/api/v1/trending?{}", params.join("&"))
+        };
+        self.timed(Endpoint::Trending, self.fetch_video_page(&endpoint))
+            .await
     }
 
     pub async fn video(&self, video_id: &str) -> Result<DetailedVideo, Error> {
-        let mut response = HTTP_CLIENT
-            .get_async(&format!("{}/api/v1/videos/{}", self.uri, video_id))
-            .await?;
+        self.timed(Endpoint::Videos, self.fetch_video(video_id))
+            .await
+    }
+
+    async fn fetch_video(&self, video_id: &str) -> Result<DetailedVideo, Error> {
+        let mut response = get(&format!(
+            "{}/api/v1/videos/{}?local={}",
+            self.uri,
+            video_id,
+            proxy_media()
+        ))
+        .await?;
 
         if response.status() == StatusCode::OK {
             let mut data: DetailedVideo = response.json().await?;
+            data.correct_uri(self);
+            Ok(data)
+        } else {
+            Err(Error::BadStatusCode)
+        }
+    }
 
-            for video in &mut data.recommended {
+    pub async fn storyboard(&self, info: &StoryboardInfo) -> Result<Vec<StoryboardTile>, Error> {
+        let mut response = get(&info.uri).await?;
+
+        if response.status() == StatusCode::OK {
+            let mut tiles = parse_storyboard(&response.text().await?);
+            for tile in tiles.iter_mut() {
+                tile.sheet_uri = correct_uri(&self.uri, &tile.sheet_uri);
+            }
+            Ok(tiles)
+        } else {
+            Err(Error::BadStatusCode)
+        }
+    }
+
+    pub async fn search(&self, query: &str) -> Result<Vec<Content>, Error> {
+        self.timed(Endpoint::Search, self.fetch_search(query)).await
+    }
+
+    /// Videos tagged with `tag`, `page` starts at 1
+    pub async fn hashtag(&self, tag: &str, page: u32) -> Result<Vec<Video>, Error> {
+        let mut response = get(&format!(
+            "{}/api/v1/hashtag/{}?page={}",
+            self.uri,
+            urlencoding::encode(tag),
+            page
+        ))
+        .await?;
+
+        if response.status() == StatusCode::OK {
+            let mut videos = response.json::<HashtagPage>().await?.results;
+            for video in videos.iter_mut() {
                 video.correct_uri(self);
             }
+            Ok(videos)
+        } else {
+            Err(Error::BadStatusCode)
+        }
+    }
 
-            Ok(data)
+    pub async fn search_suggestions(&self, query: &str) -> Result<Vec<String>, Error> {
+        let mut response = get(&format!(
+            "{}/api/v1/search/suggestions?q={}",
+            self.uri,
+            urlencoding::encode(query)
+        ))
+        .await?;
+
+        if response.status() == StatusCode::OK {
+            Ok(response.json::<SearchSuggestions>().await?.suggestions)
         } else {
             Err(Error::BadStatusCode)
         }
     }
 
-    pub async fn search(&self, query: &str) -> Result<Vec<Content>, Error> {
-        let mut response = HTTP_CLIENT
-            .get_async(&format!(
-                "{}/api/v1/search?q={}",
-                self.uri,
-                urlencoding::encode(query)
-            ))
-            .await?;
+    async fn fetch_search(&self, query: &str) -> Result<Vec<Content>, Error> {
+        let mut response = get(&format!(
+            "{}/api/v1/search?q={}",
+            self.uri,
+            urlencoding::encode(query)
+        ))
+        .await?;
 
         if response.status() == StatusCode::OK {
             let mut data: Vec<Content> = response.json().await?;
@@ -566,9 +1249,7 @@ impl Instance {
     }
 
     pub async fn channel(&self, id: &str) -> Result<DetailedChannel, Error> {
-        let mut response = HTTP_CLIENT
-            .get_async(&format!("{}/api/v1/channels/{}", self.uri, id))
-            .await?;
+        let mut response = get(&format!("{}/api/v1/channels/{}", self.uri, id)).await?;
 
         if response.status() == StatusCode::OK {
             let mut data: DetailedChannel = response.json().await?;
@@ -579,10 +1260,36 @@ impl Instance {
         }
     }
 
+    /// Videos and playlists of one channel matching `query`, `page` starts at 1
+    pub async fn channel_search(
+        &self,
+        id: &str,
+        query: &str,
+        page: u32,
+    ) -> Result<Vec<Content>, Error> {
+        let mut response = get(&format!(
+            "{}/api/v1/channels/search/{}?q={}&page={}",
+            self.uri,
+            id,
+            urlencoding::encode(query),
+            page
+        ))
+        .await?;
+
+        if response.status() == StatusCode::OK {
+            let mut data: Vec<Content> = response.json().await?;
+
+            for item in data.iter_mut() {
+                item.correct_uri(self);
+            }
+            Ok(data)
+        } else {
+            Err(Error::BadStatusCode)
+        }
+    }
+
     pub async fn channel_playlists(&self, id: &str) -> Result<Vec<Playlist>, Error> {
-        let mut response = HTTP_CLIENT
-            .get_async(&format!("{}/api/v1/channels/{}/playlists", self.uri, id))
-            .await?;
+        let mut response = get(&format!("{}/api/v1/channels/{}/playlists", self.uri, id)).await?;
         if response.status() == StatusCode::OK {
             let data = serde_json::from_value::<Vec<Playlist>>(
                 response.json::<Value>().await?["playlists"].take(),
@@ -594,10 +1301,27 @@ impl Instance {
         }
     }
 
+    pub async fn resolve_url(&self, url: &str) -> Result<ResolvedUrl, Error> {
+        let mut response = get(&format!(
+            "{}/api/v1/resolveurl?url={}",
+            self.uri,
+            urlencoding::encode(url)
+        ))
+        .await?;
+
+        if response.status() == StatusCode::OK {
+            Ok(response.json().await?)
+        } else {
+            Err(Error::BadStatusCode)
+        }
+    }
+
     pub async fn playlist(&self, id: &str) -> Result<DetailedPlaylist, Error> {
-        let mut response = HTTP_CLIENT
-            .get_async(&format!("{}/api/v1/playlists/{}", self.uri, id))
-            .await?;
+        // Mixes aren't real playlists and the playlist endpoint doesn't know them
+        if Mix::is_mix_id(id) {
+            return Ok(self.mix(id).await?.into());
+        }
+        let mut response = get(&format!("{}/api/v1/playlists/{}", self.uri, id)).await?;
 
         if response.status() == StatusCode::OK {
             let mut data: DetailedPlaylist = response.json().await?;
@@ -607,6 +1331,18 @@ impl Instance {
             Err(Error::BadStatusCode)
         }
     }
+
+    pub async fn mix(&self, id: &str) -> Result<Mix, Error> {
+        let mut response = get(&format!("{}/api/v1/mixes/{}", self.uri, id)).await?;
+
+        if response.status() == StatusCode::OK {
+            let mut data: Mix = response.json().await?;
+            data.correct_uri(self);
+            Ok(data)
+        } else {
+            Err(Error::BadStatusCode)
+        }
+    }
 }
 
 impl InvidiousClient {
@@ -619,6 +1355,7 @@ impl InvidiousClient {
                     has_trending: Some(true),
                     has_popular: Some(true),
                     open_registrations: true,
+                    health: InstanceHealth::default(),
                 })),
             })]
         } else {
@@ -654,33 +1391,44 @@ impl InvidiousClient {
         if let Some(ref instance) = *self.selected.read().unwrap() {
             instance.clone()
         } else {
-            self.instances()
-                .choose(&mut rand::thread_rng())
-                .unwrap() // Guaranteeing that this never fails saves a lot on error handling
-                .clone()
+            // Guaranteeing that this never fails saves a lot on error handling
+            choose_by_score(&self.instances()).unwrap()
         }
     }
 
     pub fn get_trending_instance(&self) -> Result<Arc<Instance>, Error> {
-        Ok(self
-            .instances()
-            .into_iter()
-            .filter(|x| x.info.read().unwrap().has_trending.unwrap_or(false))
-            .collect::<Vec<Arc<Instance>>>()
-            .choose(&mut rand::thread_rng())
-            .ok_or(Error::InstanceNotFound)?
-            .clone())
+        choose_by_score(
+            &self
+                .instances()
+                .into_iter()
+                .filter(|x| x.info.read().unwrap().has_trending.unwrap_or(false))
+                .collect::<Instances>(),
+        )
+        .ok_or(Error::InstanceNotFound)
     }
 
     pub fn get_popular_instance(&self) -> Result<Arc<Instance>, Error> {
-        Ok(self
-            .instances()
-            .into_iter()
-            .filter(|x| x.info.read().unwrap().has_popular.unwrap_or(false))
-            .collect::<Vec<Arc<Instance>>>()
-            .choose(&mut rand::thread_rng())
-            .ok_or(Error::InstanceNotFound)?
-            .clone())
+        choose_by_score(
+            &self
+                .instances()
+                .into_iter()
+                .filter(|x| x.info.read().unwrap().has_popular.unwrap_or(false))
+                .collect::<Instances>(),
+        )
+        .ok_or(Error::InstanceNotFound)
+    }
+
+    // Health
+    pub async fn check_health(&self) {
+        join_all(self.instances().iter().map(|x| x.check_health())).await;
+    }
+
+    /// Periodically checks the health of every added instance. Never returns.
+    pub async fn monitor_health(&self, interval: Duration) {
+        loop {
+            self.check_health().await;
+            glib::timeout_future(interval).await;
+        }
     }
 
     pub fn push_instance(&self, instance: Arc<Instance>) -> Result<(), Error> {
@@ -692,6 +1440,21 @@ impl InvidiousClient {
         }
     }
 
+    /// Replaces every instance, keeping the selection if it's still there
+    pub fn set_instances(&self, new_instances: Instances) -> Result<(), Error> {
+        if new_instances.is_empty() {
+            return Err(Error::AtLeastOneInstance);
+        }
+        let mut instances = self.instances.write()?;
+        let mut selected = self.selected.write()?;
+        *selected = selected
+            .as_ref()
+            .and_then(|x| new_instances.iter().find(|y| y.uri == x.uri))
+            .cloned();
+        *instances = new_instances;
+        Ok(())
+    }
+
     pub fn remove_instance(&self, instance_uri: &str) -> Result<(), Error> {
         let mut instances = self.instances.write()?;
         let mut selected = self.selected.write()?;
@@ -734,3 +1497,153 @@ impl InvidiousClient {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(start: u32, title: &str) -> Chapter {
+        Chapter {
+            start,
+            title: title.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("0:00"), Some(0));
+        assert_eq!(parse_timestamp("12:34"), Some(754));
+        assert_eq!(parse_timestamp("1:02:03"), Some(3723));
+        assert_eq!(parse_timestamp("12"), None);
+        assert_eq!(parse_timestamp("1:2:3:4"), None);
+        assert_eq!(parse_timestamp("a:10"), None);
+        assert_eq!(parse_timestamp("75:00"), Some(4500));
+        assert_eq!(parse_timestamp("0:75"), None);
+        assert_eq!(parse_timestamp("1:99:00"), None);
+    }
+
+    #[test]
+    fn parses_leading_timestamps() {
+        let description = "Chapters:\n0:00 Intro\n• 1:30 - Part one\n(1:02:03) Finale";
+        assert_eq!(
+            parse_chapters(description),
+            vec![
+                chapter(0, "Intro"),
+                chapter(90, "Part one"),
+                chapter(3723, "Finale")
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_trailing_timestamps() {
+        let description = "Intro 00:00\nMiddle - 05:00\nEnd | 10:00";
+        assert_eq!(
+            parse_chapters(description),
+            vec![
+                chapter(0, "Intro"),
+                chapter(300, "Middle"),
+                chapter(600, "End")
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_timestamps_that_go_backwards() {
+        let description = "0:00 A\n1:00 B\nLike I said at 0:30\n2:00 C";
+        assert_eq!(
+            parse_chapters(description),
+            vec![chapter(0, "A"), chapter(60, "B"), chapter(120, "C")]
+        );
+    }
+
+    #[test]
+    fn needs_a_title() {
+        assert!(parse_chapters("00:00\n1:00 B\n2:00 C").is_empty());
+        assert_eq!(
+            parse_chapters("00:00\n0:00 A\n1:00:00\n1:00 B\n2:00 C"),
+            vec![chapter(0, "A"), chapter(60, "B"), chapter(120, "C")]
+        );
+    }
+
+    #[test]
+    fn needs_three_chapters_from_the_start() {
+        assert!(parse_chapters("0:00 A\n1:00 B").is_empty());
+        assert!(parse_chapters("0:10 A\n1:00 B\n2:00 C").is_empty());
+        assert!(parse_chapters("No chapters here").is_empty());
+    }
+
+    const STORYBOARD: &str = "WEBVTT
+
+00:00:00.000 --> 00:00:05.000
+https://invidious.example.org/sb/i/abc/storyboard/M0.jpg#xywh=0,0,160,90
+
+00:00:05.000 --> 00:00:10.000
+https://invidious.example.org/sb/i/abc/storyboard/M0.jpg#xywh=160,0,160,90
+
+01:02:03.500 --> 01:02:08.500
+https://invidious.example.org/sb/i/abc/storyboard/M1.jpg#xywh=0,90,160,90
+";
+
+    #[test]
+    fn parses_storyboards() {
+        let tiles = parse_storyboard(STORYBOARD);
+        assert_eq!(tiles.len(), 3);
+        assert_eq!((tiles[0].start, tiles[0].end), (0.0, 5.0));
+        assert_eq!((tiles[1].x, tiles[1].y), (160, 0));
+        assert_eq!((tiles[1].width, tiles[1].height), (160, 90));
+        assert_eq!(tiles[2].start, 3723.5);
+        assert_eq!(
+            tiles[2].sheet_uri,
+            "https://invidious.example.org/sb/i/abc/storyboard/M1.jpg"
+        );
+    }
+
+    #[test]
+    fn skips_malformed_storyboard_cues() {
+        let vtt = "WEBVTT
+
+00:00:00.000 --> 00:0a:05.000
+https://invidious.example.org/M0.jpg#xywh=0,0,160,90
+
+00:00:05.000 --> 00:00:10.000
+https://invidious.example.org/M0.jpg#xywh=0,0,99999999999,90
+
+00:00:10.000 --> 00:00:15.000
+https://invidious.example.org/M0.jpg
+
+00:00:15.000 --> 00:00:20.000
+https://invidious.example.org/M0.jpg#xywh=320,0,160,90
+";
+        let tiles = parse_storyboard(vtt);
+        assert_eq!(tiles.len(), 1);
+        assert_eq!(tiles[0].start, 15.0);
+        assert_eq!(tiles[0].x, 320);
+    }
+
+    #[test]
+    fn parses_empty_storyboards() {
+        assert!(parse_storyboard("").is_empty());
+        assert!(parse_storyboard("WEBVTT\n").is_empty());
+    }
+
+    #[test]
+    fn proxies_media_with_ports() {
+        let instance = "https://invidious.example.org";
+        assert_eq!(
+            proxy_uri(
+                instance,
+                "https://rr1.googlevideo.com:443/videoplayback?id=1"
+            ),
+            "https://invidious.example.org/videoplayback?id=1&host=rr1.googlevideo.com"
+        );
+        assert_eq!(
+            proxy_uri(instance, "https://i.ytimg.com:8443/vi/abc/hqdefault.jpg"),
+            "https://invidious.example.org/vi/abc/hqdefault.jpg"
+        );
+        assert_eq!(
+            proxy_uri(instance, "https://example.com:8080/image.jpg"),
+            "https://example.com:8080/image.jpg"
+        );
+    }
+}
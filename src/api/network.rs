@@ -0,0 +1,163 @@
+use isahc::config::{Configurable, RedirectPolicy};
+use isahc::http::Uri;
+use isahc::prelude::*;
+use isahc::{AsyncBody, HttpClient, Response};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+use super::Error;
+
+const PROXY_SCHEMES: [&str; 6] = ["http", "https", "socks4", "socks4a", "socks5", "socks5h"];
+
+/// How requests leave this computer
+#[derive(Debug, Clone, Default)]
+enum Route {
+    #[default]
+    Direct,
+    Proxy(Uri),
+    // A proxy was asked for but can't be used, so nothing goes out at all
+    Blocked,
+}
+
+lazy_static! {
+    static ref HTTP_CLIENT: RwLock<HttpClient> = RwLock::new(build_client(None).unwrap());
+    static ref ROUTE: RwLock<Route> = RwLock::new(Route::default());
+    // Number of requests made to each host since startup, for auditing
+    static ref CONTACTED_HOSTS: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+}
+
+#[derive(Debug, Deserialize)]
+struct TorCheckResponse {
+    #[serde(rename = "IsTor")]
+    is_tor: bool,
+}
+
+fn build_client(proxy: Option<Uri>) -> Result<HttpClient, Error> {
+    Ok(HttpClient::builder()
+        .timeout(Duration::from_secs(10))
+        .redirect_policy(RedirectPolicy::Limit(10))
+        .proxy(proxy)
+        .build()?)
+}
+
+/// Client used for every request Pryvid makes (API calls, images, ...)
+fn http_client() -> HttpClient {
+    HTTP_CLIENT.read().unwrap().clone()
+}
+
+/// Sends a GET request with the shared client and records the host that was contacted
+pub async fn get(uri: impl AsRef<str>) -> Result<Response<AsyncBody>, Error> {
+    if requests_blocked() {
+        return Err(Error::ProxyUnavailable);
+    }
+    let uri = uri.as_ref();
+    record_host(uri);
+    Ok(http_client().get_async(uri).await?)
+}
+
+/// Records a request to the host of `uri`. Media streams that aren't fetched
+/// with `get` should be recorded manually.
+pub fn record_host(uri: &str) {
+    if let Some(host) = uri
+        .parse::<Uri>()
+        .ok()
+        .and_then(|x| x.host().map(String::from))
+    {
+        if let Ok(mut hosts) = CONTACTED_HOSTS.lock() {
+            *hosts.entry(host).or_default() += 1;
+        }
+    }
+}
+
+pub fn contacted_hosts() -> Vec<(String, u64)> {
+    CONTACTED_HOSTS
+        .lock()
+        .map(|hosts| hosts.iter().map(|(x, y)| (x.clone(), *y)).collect())
+        .unwrap_or_default()
+}
+
+pub fn clear_contacted_hosts() {
+    if let Ok(mut hosts) = CONTACTED_HOSTS.lock() {
+        hosts.clear();
+    }
+}
+
+/// Routes every request through `proxy`, or connects directly if it is `None`. Requests are
+/// blocked until another proxy is set if `proxy` can't be used.
+pub fn set_proxy(proxy: Option<&str>) -> Result<(), Error> {
+    let result = parse_proxy(proxy).and_then(|proxy| {
+        let client = build_client(proxy.clone())?;
+        *HTTP_CLIENT.write()? = client;
+        Ok(proxy)
+    });
+    *ROUTE.write()? = match &result {
+        Ok(Some(proxy)) => Route::Proxy(proxy.clone()),
+        Ok(None) => Route::Direct,
+        Err(_) => Route::Blocked,
+    };
+    result.map(|_| ())
+}
+
+/// Stops all requests, for when the chosen proxy mode has no usable proxy
+pub fn block_requests() {
+    if let Ok(mut route) = ROUTE.write() {
+        *route = Route::Blocked;
+    }
+}
+
+pub fn requests_blocked() -> bool {
+    ROUTE
+        .read()
+        .map_or(true, |route| matches!(*route, Route::Blocked))
+}
+
+/// How media streams have to connect, with the proxy in the form GStreamer's HTTP source takes
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaRoute {
+    Direct,
+    Proxy(String),
+    Blocked,
+}
+
+pub fn media_route() -> MediaRoute {
+    match ROUTE.read().as_deref() {
+        Ok(Route::Direct) => MediaRoute::Direct,
+        // GIO resolves hostnames through SOCKS5 proxies anyway and doesn't know socks5h
+        Ok(Route::Proxy(uri)) => {
+            MediaRoute::Proxy(uri.to_string().replacen("socks5h://", "socks5://", 1))
+        }
+        Ok(Route::Blocked) | Err(_) => MediaRoute::Blocked,
+    }
+}
+
+fn parse_proxy(proxy: Option<&str>) -> Result<Option<Uri>, Error> {
+    let Some(proxy) = proxy else {
+        return Ok(None);
+    };
+    // GIO reports SOCKS proxies without a version, hostnames shouldn't be resolved locally
+    let proxy = match proxy.trim().strip_prefix("socks://") {
+        Some(rest) => format!("socks5h://{}", rest),
+        None => proxy.trim().to_string(),
+    };
+    let uri: Uri = proxy.parse().map_err(|_| Error::InvalidProxy)?;
+    if !uri
+        .scheme_str()
+        .is_some_and(|scheme| PROXY_SCHEMES.contains(&scheme))
+    {
+        return Err(Error::InvalidProxy);
+    }
+    Ok(Some(uri))
+}
+
+/// Asks the Tor Project whether requests are currently coming out of the Tor network
+pub async fn check_tor() -> Result<bool, Error> {
+    let mut response = get("https://check.torproject.org/api/ip").await?;
+    if response.status().is_success() {
+        Ok(response.json::<TorCheckResponse>().await?.is_tor)
+    } else {
+        Err(Error::BadStatusCode)
+    }
+}
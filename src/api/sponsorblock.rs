@@ -0,0 +1,200 @@
+use isahc::http::StatusCode;
+use isahc::prelude::*;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::{get, Error};
+
+// Only this many characters of the video ID hash are sent, so the server can't tell
+// which video is being watched
+const HASH_PREFIX_LENGTH: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SegmentCategory {
+    Sponsor,
+    SelfPromo,
+    Interaction,
+    Intro,
+    Outro,
+    Preview,
+    MusicOfftopic,
+    Filler,
+}
+
+impl SegmentCategory {
+    pub const ALL: [SegmentCategory; 8] = [
+        SegmentCategory::Sponsor,
+        SegmentCategory::SelfPromo,
+        SegmentCategory::Interaction,
+        SegmentCategory::Intro,
+        SegmentCategory::Outro,
+        SegmentCategory::Preview,
+        SegmentCategory::MusicOfftopic,
+        SegmentCategory::Filler,
+    ];
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| x.id() == id)
+    }
+
+    /// Name used by the SponsorBlock API and in settings
+    pub fn id(&self) -> &'static str {
+        match self {
+            SegmentCategory::Sponsor => "sponsor",
+            SegmentCategory::SelfPromo => "selfpromo",
+            SegmentCategory::Interaction => "interaction",
+            SegmentCategory::Intro => "intro",
+            SegmentCategory::Outro => "outro",
+            SegmentCategory::Preview => "preview",
+            SegmentCategory::MusicOfftopic => "music_offtopic",
+            SegmentCategory::Filler => "filler",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            SegmentCategory::Sponsor => "Sponsor",
+            SegmentCategory::SelfPromo => "Self Promotion",
+            SegmentCategory::Interaction => "Interaction Reminder",
+            SegmentCategory::Intro => "Intermission/Intro",
+            SegmentCategory::Outro => "Endcards/Credits",
+            SegmentCategory::Preview => "Preview/Recap",
+            SegmentCategory::MusicOfftopic => "Non-Music Section",
+            SegmentCategory::Filler => "Filler Tangent",
+        }
+    }
+
+    /// The colors SponsorBlock uses for each category, so segments look familiar
+    pub fn color(&self) -> &'static str {
+        match self {
+            SegmentCategory::Sponsor => "#00d400",
+            SegmentCategory::SelfPromo => "#ffff00",
+            SegmentCategory::Interaction => "#cc00ff",
+            SegmentCategory::Intro => "#00ffff",
+            SegmentCategory::Outro => "#0202ed",
+            SegmentCategory::Preview => "#008fd6",
+            SegmentCategory::MusicOfftopic => "#ff9900",
+            SegmentCategory::Filler => "#7300ff",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentAction {
+    Skip,
+    Button,
+    Mark,
+    Ignore,
+}
+
+impl SegmentAction {
+    // Order matches the category rows in preferences
+    pub const ALL: [SegmentAction; 4] = [
+        SegmentAction::Skip,
+        SegmentAction::Button,
+        SegmentAction::Mark,
+        SegmentAction::Ignore,
+    ];
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| x.id() == id)
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            SegmentAction::Skip => "skip",
+            SegmentAction::Button => "button",
+            SegmentAction::Mark => "mark",
+            SegmentAction::Ignore => "ignore",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            SegmentAction::Skip => "Skip Automatically",
+            SegmentAction::Button => "Show Skip Button",
+            SegmentAction::Mark => "Mark on Seek Bar",
+            SegmentAction::Ignore => "Ignore",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub uuid: String,
+    pub category: SegmentCategory,
+    // Seconds
+    pub start: f64,
+    pub end: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct VideoSegments {
+    #[serde(rename = "videoID")]
+    video_id: String,
+    segments: Vec<SegmentResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SegmentResponse {
+    #[serde(rename = "UUID")]
+    uuid: String,
+    category: String,
+    #[serde(rename = "actionType")]
+    action_type: String,
+    segment: (f64, f64),
+}
+
+// DeArrow is run by the same people and uses the same scheme
+pub(super) fn hash_prefix(video_id: &str) -> String {
+    let hash = Sha256::digest(video_id.as_bytes());
+    hash.iter()
+        .map(|x| format!("{:02x}", x))
+        .collect::<String>()[..HASH_PREFIX_LENGTH]
+        .to_string()
+}
+
+/// Fetches the skippable segments of a video from a SponsorBlock server, using the
+/// hash prefix endpoint so the server only learns a range of videos
+pub async fn skip_segments(
+    server: &str,
+    video_id: &str,
+    categories: &[SegmentCategory],
+) -> Result<Vec<Segment>, Error> {
+    if categories.is_empty() {
+        return Ok(Vec::new());
+    }
+    let categories =
+        serde_json::to_string(&categories.iter().map(|x| x.id()).collect::<Vec<&str>>())?;
+    let mut response = get(&format!(
+        "{}/api/skipSegments/{}?categories={}&actionTypes={}",
+        server.trim_end_matches('/'),
+        hash_prefix(video_id),
+        urlencoding::encode(&categories),
+        urlencoding::encode("[\"skip\"]"),
+    ))
+    .await?;
+
+    match response.status() {
+        StatusCode::OK => {
+            let videos: Vec<VideoSegments> = response.json().await?;
+            Ok(videos
+                .into_iter()
+                .filter(|x| x.video_id == video_id)
+                .flat_map(|x| x.segments)
+                .filter(|x| x.action_type == "skip")
+                .filter_map(|x| {
+                    Some(Segment {
+                        uuid: x.uuid,
+                        category: SegmentCategory::from_id(&x.category)?,
+                        start: x.segment.0,
+                        end: x.segment.1,
+                    })
+                })
+                .collect())
+        }
+        // No video with this prefix has segments
+        StatusCode::NOT_FOUND => Ok(Vec::new()),
+        _ => Err(Error::BadStatusCode),
+    }
+}
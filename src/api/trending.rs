@@ -0,0 +1,152 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrendingCategory {
+    Default,
+    Music,
+    Gaming,
+    Movies,
+}
+
+impl TrendingCategory {
+    pub const ALL: [TrendingCategory; 4] = [
+        TrendingCategory::Default,
+        TrendingCategory::Music,
+        TrendingCategory::Gaming,
+        TrendingCategory::Movies,
+    ];
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| x.id() == id)
+    }
+
+    /// Name used by the Invidious API and for stack pages
+    pub fn id(&self) -> &'static str {
+        match self {
+            TrendingCategory::Default => "default",
+            TrendingCategory::Music => "music",
+            TrendingCategory::Gaming => "gaming",
+            TrendingCategory::Movies => "movies",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            TrendingCategory::Default => "Now",
+            TrendingCategory::Music => "Music",
+            TrendingCategory::Gaming => "Gaming",
+            TrendingCategory::Movies => "Movies",
+        }
+    }
+}
+
+/// Countries YouTube has trending videos for, as ISO 3166 codes and names
+pub const REGIONS: [(&str, &str); 109] = [
+    ("DZ", "Algeria"),
+    ("AR", "Argentina"),
+    ("AU", "Australia"),
+    ("AT", "Austria"),
+    ("AZ", "Azerbaijan"),
+    ("BH", "Bahrain"),
+    ("BD", "Bangladesh"),
+    ("BY", "Belarus"),
+    ("BE", "Belgium"),
+    ("BO", "Bolivia"),
+    ("BA", "Bosnia and Herzegovina"),
+    ("BR", "Brazil"),
+    ("BG", "Bulgaria"),
+    ("KH", "Cambodia"),
+    ("CA", "Canada"),
+    ("CL", "Chile"),
+    ("CO", "Colombia"),
+    ("CR", "Costa Rica"),
+    ("HR", "Croatia"),
+    ("CY", "Cyprus"),
+    ("CZ", "Czechia"),
+    ("DK", "Denmark"),
+    ("DO", "Dominican Republic"),
+    ("EC", "Ecuador"),
+    ("EG", "Egypt"),
+    ("SV", "El Salvador"),
+    ("EE", "Estonia"),
+    ("FI", "Finland"),
+    ("FR", "France"),
+    ("GE", "Georgia"),
+    ("DE", "Germany"),
+    ("GH", "Ghana"),
+    ("GR", "Greece"),
+    ("GT", "Guatemala"),
+    ("HN", "Honduras"),
+    ("HK", "Hong Kong"),
+    ("HU", "Hungary"),
+    ("IS", "Iceland"),
+    ("IN", "India"),
+    ("ID", "Indonesia"),
+    ("IQ", "Iraq"),
+    ("IE", "Ireland"),
+    ("IL", "Israel"),
+    ("IT", "Italy"),
+    ("JM", "Jamaica"),
+    ("JP", "Japan"),
+    ("JO", "Jordan"),
+    ("KZ", "Kazakhstan"),
+    ("KE", "Kenya"),
+    ("KW", "Kuwait"),
+    ("LA", "Laos"),
+    ("LV", "Latvia"),
+    ("LB", "Lebanon"),
+    ("LY", "Libya"),
+    ("LI", "Liechtenstein"),
+    ("LT", "Lithuania"),
+    ("LU", "Luxembourg"),
+    ("MY", "Malaysia"),
+    ("MT", "Malta"),
+    ("MX", "Mexico"),
+    ("MD", "Moldova"),
+    ("ME", "Montenegro"),
+    ("MA", "Morocco"),
+    ("NP", "Nepal"),
+    ("NL", "Netherlands"),
+    ("NZ", "New Zealand"),
+    ("NI", "Nicaragua"),
+    ("NG", "Nigeria"),
+    ("MK", "North Macedonia"),
+    ("NO", "Norway"),
+    ("OM", "Oman"),
+    ("PK", "Pakistan"),
+    ("PA", "Panama"),
+    ("PG", "Papua New Guinea"),
+    ("PY", "Paraguay"),
+    ("PE", "Peru"),
+    ("PH", "Philippines"),
+    ("PL", "Poland"),
+    ("PT", "Portugal"),
+    ("PR", "Puerto Rico"),
+    ("QA", "Qatar"),
+    ("RO", "Romania"),
+    ("RU", "Russia"),
+    ("SA", "Saudi Arabia"),
+    ("SN", "Senegal"),
+    ("RS", "Serbia"),
+    ("SG", "Singapore"),
+    ("SK", "Slovakia"),
+    ("SI", "Slovenia"),
+    ("ZA", "South Africa"),
+    ("KR", "South Korea"),
+    ("ES", "Spain"),
+    ("LK", "Sri Lanka"),
+    ("SE", "Sweden"),
+    ("CH", "Switzerland"),
+    ("TW", "Taiwan"),
+    ("TZ", "Tanzania"),
+    ("TH", "Thailand"),
+    ("TN", "Tunisia"),
+    ("TR", "Turkey"),
+    ("UG", "Uganda"),
+    ("UA", "Ukraine"),
+    ("AE", "United Arab Emirates"),
+    ("GB", "United Kingdom"),
+    ("US", "United States"),
+    ("UY", "Uruguay"),
+    ("VE", "Venezuela"),
+    ("VN", "Vietnam"),
+    ("YE", "Yemen"),
+];
@@ -1,24 +1,34 @@
 use adw::prelude::*;
 use adw::subclass::prelude::*;
 use gio::Settings;
-use glib::BoolError;
+use glib::{clone, MainContext};
 use gtk::{gio, glib};
-use std::cell::OnceCell;
+use std::cell::{OnceCell, RefCell};
+use std::fs;
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::api::{Instances, InvidiousClient};
+use crate::api::{self, InvidiousClient};
 use crate::appmodel::AppModel;
 use crate::config::{APP_ID, VERSION};
+use crate::instance_state::InstanceState;
+use crate::profiles::{Profiles, ProfilesError};
 use crate::widgets::{
     onboarding::OnboardingWindow, preferences::PryvidPreferencesWindow, window::PryvidWindow,
 };
 
+// Seconds between saves of the instances, their statistics and changed profiles
+const AUTOSAVE_INTERVAL: u32 = 60;
+const PROXY_BLOCKED_MESSAGE: &str = "The proxy can't be used, nothing is sent until it is fixed";
+
 mod imp {
     use super::*;
 
     #[derive(Debug, Default)]
     pub struct PryvidApplication {
         pub model: OnceCell<Arc<AppModel>>,
+        // Shown once the main window is open
+        pub load_errors: RefCell<Vec<String>>,
     }
 
     #[glib::object_subclass]
@@ -62,11 +72,23 @@ mod imp {
 
             // Ask the window manager/compositor to present the window
             window.present();
+
+            for message in self.load_errors.take() {
+                application.show_message(&message);
+            }
+            if api::requests_blocked() {
+                application.show_message(PROXY_BLOCKED_MESSAGE);
+            }
         }
 
         fn shutdown(&self) {
             self.parent_shutdown();
-            self.obj().save_instances().unwrap();
+            let model = self.obj().model();
+            if let Err(error) = model.save_instances() {
+                log::warn!("Failed to save instances: {}", error);
+            }
+            model.remember_selected_instance();
+            model.save_changed_profiles();
         }
     }
 
@@ -98,48 +120,143 @@ impl PryvidApplication {
 
     fn setup_model(&self) {
         // Setup Invidious
-        // TODO: Handle errors a little better
         let settings = Settings::new(APP_ID);
-        let instances = self.load_instances(&settings).unwrap();
-        let invidious = InvidiousClient::new(instances);
-        invidious
-            .select_instance_by_name(settings.string("selected").as_str())
-            .unwrap();
-        let model = Arc::new(AppModel::new(invidious, settings));
+        self.setup_proxy(&settings);
+        let state = self.load_instances(&settings);
+        let invidious = InvidiousClient::new(state.instances);
+        if let Some(selected) = &state.selected {
+            if let Err(error) = invidious.select_instance_by_name(selected) {
+                log::warn!("Failed to select saved instance: {}", error);
+            }
+        }
+        let health_interval = Duration::from_secs(settings.uint("health-check-interval").into());
+        let profiles = self.load_profiles();
+        let model = Arc::new(AppModel::new(invidious, settings, profiles));
+
+        // Keep instance health statistics up to date in the background
+        let invidious = model.invidious();
+        MainContext::default().spawn_local(async move {
+            invidious.monitor_health(health_interval).await;
+        });
+
+        // Statistics change all the time, so they're saved regularly rather than only on
+        // shutdown, which doesn't happen if Pryvid crashes
+        let autosave_model = model.clone();
+        glib::timeout_add_seconds_local(AUTOSAVE_INTERVAL, move || {
+            if let Err(error) = autosave_model.save_instances() {
+                log::warn!("Failed to save instances: {}", error);
+            }
+            autosave_model.save_changed_profiles();
+            glib::ControlFlow::Continue
+        });
+
         self.imp()
             .model
             .set(model)
             .expect("`model` should not be set before calling `setup_model`");
     }
 
-    fn save_instances(&self) -> Result<(), BoolError> {
-        let invidious = self.model().invidious();
-        let settings = self.model().settings();
-        let instances = invidious.instances();
-        settings.set(
-            "instances",
-            serde_json::to_string(&instances.to_vec())
-                .unwrap()
-                .to_string(),
-        )?;
-        settings.set(
-            "selected",
-            if let Some(instance) = invidious.selected_instance() {
-                instance.uri.clone()
-            } else {
-                "".into()
-            },
-        )?;
-        Ok(())
+    fn setup_proxy(&self, settings: &Settings) {
+        self.apply_proxy(settings);
+        for key in ["proxy-mode", "proxy-uri", "tor-proxy-uri"] {
+            settings.connect_changed(
+                Some(key),
+                clone!(@weak self as app => move |settings, _| app.apply_proxy(settings)),
+            );
+        }
+
+        api::set_proxy_media(settings.boolean("proxy-media"));
+        settings.connect_changed(Some("proxy-media"), |settings, key| {
+            api::set_proxy_media(settings.boolean(key));
+        });
     }
 
-    fn load_instances(&self, settings: &Settings) -> Result<Instances, serde_json::Error> {
-        // --- Only used in testing
-        settings.set_boolean("first-run", true).unwrap();
-        // settings.reset("instances");
-        // ---
+    /// Uses the proxy chosen in the settings. If it can't be used nothing is sent at all, as
+    /// connecting directly would give away who is watching
+    fn apply_proxy(&self, settings: &Settings) {
+        let result =
+            proxy_from_settings(settings).and_then(|proxy| api::set_proxy(proxy.as_deref()));
+        if let Err(error) = result {
+            api::block_requests();
+            log::warn!("Blocking requests, the proxy can't be used: {error}");
+            self.show_message(PROXY_BLOCKED_MESSAGE);
+        }
+    }
+
+    /// Shows `message` in the main window, if it is open
+    fn show_message(&self, message: &str) {
+        let Some(window) = self.active_window() else {
+            return;
+        };
+        if window.is::<PryvidWindow>() {
+            if let Err(error) = window.activate_action("win.notify", Some(&message.to_variant())) {
+                log::warn!("Failed to show \"{message}\": {error}");
+            }
+        } else {
+            // Onboarding has nowhere to show toasts
+            let dialog = adw::MessageDialog::builder()
+                .transient_for(&window)
+                .modal(true)
+                .body(message)
+                .build();
+            dialog.add_response("close", "_Close");
+            dialog.present();
+        }
+    }
+
+    /// Reads the saved instances. If they can't be read, onboarding runs again so new ones
+    /// can be picked
+    fn load_instances(&self, settings: &Settings) -> InstanceState {
+        let json = settings.string("instances");
+        match InstanceState::from_json(&json, &settings.string("selected")) {
+            Ok(state) => state,
+            Err(error) => {
+                log::warn!("Failed to read instances: {}", error);
+                // Kept so the instances can still be recovered by hand
+                let path = glib::user_data_dir()
+                    .join("pryvid")
+                    .join("instances.broken.json");
+                if let Err(error) = path
+                    .parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| fs::write(&path, json.as_str()))
+                {
+                    log::warn!("Failed to keep broken instances: {}", error);
+                }
+                self.imp().load_errors.borrow_mut().push(format!(
+                    "Your instances couldn't be read ({}), so they have to be set up again. \
+                     A copy was kept in {}",
+                    error,
+                    path.display()
+                ));
+                // There are no instances to use, so they are set up like on the first run
+                if let Err(error) = settings.set_boolean("first-run", true) {
+                    log::warn!("Failed to start onboarding again: {}", error);
+                }
+                InstanceState::default()
+            }
+        }
+    }
 
-        serde_json::from_str(&settings.string("instances"))
+    /// Reads the saved profiles. If they can't be read, a copy is kept and the user is told
+    /// that changes to profiles won't be saved
+    fn load_profiles(&self) -> Result<Option<Profiles>, ProfilesError> {
+        let profiles = Profiles::load();
+        if let Err(error) = &profiles {
+            log::warn!("Failed to read profiles: {}", error);
+            let kept = match Profiles::keep_broken() {
+                Ok(path) => format!("A copy was kept in {}", path.display()),
+                Err(error) => {
+                    log::warn!("Failed to keep broken profiles: {}", error);
+                    "The file was left as it is".to_string()
+                }
+            };
+            self.imp().load_errors.borrow_mut().push(format!(
+                "Your profiles couldn't be read ({}), so changes to them won't be saved. {}",
+                error, kept
+            ));
+        }
+        profiles
     }
 
     fn setup_gactions(&self) {
@@ -163,22 +280,57 @@ impl PryvidApplication {
                 if let Some(active_window) = app.active_window() {
                     active_window.close()
                 }
+                let model = app.model();
+                model.settings().set_boolean("first-run", false).unwrap();
+                if let Err(error) = model.save_instances() {
+                    log::warn!("Failed to save instances: {}", error);
+                }
                 let window = PryvidWindow::new(&*app, app.model());
                 window.present();
             })
             .build();
+        // Used by the notification shown while playing in the background
+        let show_window_action = gio::ActionEntry::builder("show-window")
+            .activate(move |app: &Self, _, _| {
+                if let Some(window) = app.main_window() {
+                    window.show_from_background();
+                }
+            })
+            .build();
+        let stop_playback_action = gio::ActionEntry::builder("stop-playback")
+            .activate(move |app: &Self, _, _| {
+                if let Some(window) = app.main_window() {
+                    window.stop_background_playback();
+                }
+            })
+            .build();
         self.add_action_entries([
             quit_action,
             about_action,
             preferences_action,
             getstarted_action,
+            show_window_action,
+            stop_playback_action,
         ]);
     }
 
+    // The active window may be hidden, so look through all of them
+    fn main_window(&self) -> Option<PryvidWindow> {
+        self.windows()
+            .into_iter()
+            .find_map(|window| window.downcast::<PryvidWindow>().ok())
+    }
+
     fn setup_accels(&self) {
         self.set_accels_for_action("app.quit", &["<primary>q"]);
-        self.set_accels_for_action("win.toggle-fullscreen", &["f"]);
+        self.set_accels_for_action("win.start-search", &["<primary>f"]);
+        self.set_accels_for_action("win.go-back", &["<alt>Left"]);
         self.set_accels_for_action("win.escape-pressed", &["Escape"]);
+
+        // Player keys without modifiers are handled by the video page itself
+        self.set_accels_for_action("win.play-next", &["<shift>n"]);
+        self.set_accels_for_action("win.next-chapter", &["<primary>Right"]);
+        self.set_accels_for_action("win.previous-chapter", &["<primary>Left"]);
     }
 
     fn show_about(&self) {
@@ -196,3 +348,32 @@ impl PryvidApplication {
         about.present();
     }
 }
+
+/// Proxy chosen in the settings, `None` for a direct connection
+fn proxy_from_settings(settings: &Settings) -> Result<Option<String>, api::Error> {
+    match settings.string("proxy-mode").as_str() {
+        "system" => {
+            let proxy = gio::ProxyResolver::default()
+                .lookup("https://api.invidious.io", None::<&gio::Cancellable>)
+                .map_err(|error| {
+                    log::warn!("Failed to look up the system proxy: {error}");
+                    api::Error::ProxyUnavailable
+                })?
+                .first()
+                .map(|x| x.to_string())
+                .ok_or(api::Error::ProxyUnavailable)?;
+            Ok(Some(proxy).filter(|proxy| proxy != "direct://"))
+        }
+        "manual" => match settings.string("proxy-uri").trim() {
+            "" => Err(api::Error::InvalidProxy),
+            uri => Ok(Some(uri.to_string())),
+        },
+        // Hostnames have to be resolved by Tor for .onion addresses to work
+        "tor" => Ok(Some(settings.string("tor-proxy-uri").replacen(
+            "socks5://",
+            "socks5h://",
+            1,
+        ))),
+        _ => Ok(None),
+    }
+}
@@ -1,21 +1,63 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use gtk::gio::Settings;
+use gtk::glib::BoolError;
+use gtk::prelude::*;
 
-use crate::api::InvidiousClient;
+use crate::api::sponsorblock::{SegmentAction, SegmentCategory};
+use crate::api::{DetailedVideo, InvidiousClient};
+use crate::backup::{Backup, RestoreMode};
+use crate::content_filter::ContentFilter;
+use crate::instance_state::InstanceState;
+use crate::profiles::{
+    now, HistoryEntry, LocalPlaylist, PlaylistVideo, Profile, Profiles, ProfilesError, Subscription,
+};
 
 #[derive(Debug)]
 pub struct AppModel {
     invidious: Arc<InvidiousClient>,
     settings: Arc<Settings>,
+    profiles: Mutex<Profiles>,
+    // Set when a profile changed since profiles.json was last written
+    profiles_changed: AtomicBool,
+    // Set when profiles.json couldn't be read, so it's never written over
+    profiles_unreadable: bool,
 }
 
 impl AppModel {
-    pub fn new(invidious: InvidiousClient, settings: Settings) -> Self {
-        AppModel {
+    /// `saved` is what `Profiles::load` returned. If it failed, a new profile is used for now
+    pub fn new(
+        invidious: InvidiousClient,
+        settings: Settings,
+        saved: Result<Option<Profiles>, ProfilesError>,
+    ) -> Self {
+        let profiles_unreadable = saved.is_err();
+        let profiles = saved.ok().flatten().unwrap_or_else(|| {
+            // Everything set up before profiles existed goes into the first one
+            let mut profile = Profile::new("Default");
+            profile.filter = ContentFilter::from_json(&settings.string("content-filter"));
+            profile.instance = invidious.selected_instance().map(|x| x.uri.clone());
+            Profiles {
+                profiles: vec![profile],
+            }
+        });
+        if profiles.get(&settings.string("active-profile")).is_none() {
+            settings
+                .set_string("active-profile", &profiles.profiles[0].id)
+                .unwrap();
+        }
+
+        let model = AppModel {
             invidious: Arc::new(invidious),
             settings: Arc::new(settings),
-        }
+            profiles: Mutex::new(profiles),
+            profiles_changed: AtomicBool::new(false),
+            profiles_unreadable,
+        };
+        model.save_profiles();
+        model
     }
 
     pub fn invidious(&self) -> Arc<InvidiousClient> {
@@ -25,4 +67,362 @@ impl AppModel {
     pub fn settings(&self) -> Arc<Settings> {
         self.settings.clone()
     }
+
+    pub fn save_instances(&self) -> Result<(), BoolError> {
+        let state = InstanceState::new(
+            self.invidious.instances(),
+            self.invidious.selected_instance().map(|x| x.uri.clone()),
+        );
+        self.settings.set_string("instances", &state.to_json())
+    }
+
+    pub fn backup(&self) -> Backup {
+        self.remember_selected_instance();
+        Backup::new(
+            self.invidious.instances(),
+            self.invidious.selected_instance().map(|x| x.uri.clone()),
+            &self.settings,
+            self.profiles(),
+            self.active_profile_id(),
+        )
+    }
+
+    /// Profiles with a PIN other than the active one are left as they are, as changing them
+    /// would need their PIN. Returns a locked profile the backup would have made active. It
+    /// isn't switched to before its PIN is entered, unless every profile is locked
+    pub fn restore(&self, backup: Backup, mode: RestoreMode) -> Option<String> {
+        let active = self.active_profile_id();
+        match mode {
+            RestoreMode::Replace => {
+                backup.apply_settings(&self.settings);
+                if let Err(error) = self.invidious.set_instances(backup.instances) {
+                    log::warn!("Keeping current instances: {}", error);
+                }
+                if let Some(uri) = &backup.selected {
+                    if let Err(error) = self.invidious.select_instance_by_name(uri) {
+                        log::warn!("Failed to select backed up instance: {}", error);
+                    }
+                }
+                if !backup.profiles.is_empty() {
+                    let mut profiles = self.lock_profiles();
+                    let kept: Vec<Profile> = profiles
+                        .profiles
+                        .drain(..)
+                        .filter(|x| is_protected(x, &active))
+                        .collect();
+                    profiles.profiles = backup
+                        .profiles
+                        .into_iter()
+                        .filter(|x| !kept.iter().any(|kept| kept.id == x.id))
+                        .chain(kept)
+                        .collect();
+                }
+            }
+            RestoreMode::Merge => {
+                for instance in backup.instances {
+                    // Instances that were already added are left as they are
+                    let _ = self.invidious.push_instance(instance);
+                }
+                let mut profiles = self.lock_profiles();
+                for profile in backup.profiles {
+                    match profiles.get_mut(&profile.id) {
+                        Some(existing) if is_protected(existing, &active) => {}
+                        Some(existing) => existing.merge(profile),
+                        None => profiles.profiles.push(profile),
+                    }
+                }
+            }
+        }
+        self.save_profiles();
+        if let Err(error) = self.save_instances() {
+            log::warn!("Failed to save instances: {}", error);
+        }
+
+        // Stays in the same profile if the backup has it too
+        let profiles = self.profiles();
+        let wanted = [Some(active), backup.active_profile]
+            .into_iter()
+            .flatten()
+            .find_map(|id| profiles.iter().find(|x| x.id == id))
+            .unwrap_or(&profiles[0]);
+        if !wanted.is_locked() {
+            self.activate_profile(&wanted.id);
+            return None;
+        }
+        // The backup may have a PIN for a profile that had none, so it's asked for again
+        let fallback = profiles.iter().find(|x| !x.is_locked()).unwrap_or(wanted);
+        self.activate_profile(&fallback.id);
+        Some(wanted.id.clone())
+    }
+
+    /// What to do with each SponsorBlock category, categories missing from settings are ignored
+    pub fn segment_actions(&self) -> HashMap<SegmentCategory, SegmentAction> {
+        self.settings
+            .value("sponsorblock-categories")
+            .get::<HashMap<String, String>>()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|(category, action)| {
+                Some((
+                    SegmentCategory::from_id(category)?,
+                    SegmentAction::from_id(action)?,
+                ))
+            })
+            .collect()
+    }
+
+    pub fn set_segment_action(&self, category: SegmentCategory, action: SegmentAction) {
+        let mut actions: HashMap<String, String> = self
+            .settings
+            .value("sponsorblock-categories")
+            .get()
+            .unwrap_or_default();
+        actions.insert(category.id().to_string(), action.id().to_string());
+        self.settings
+            .set_value("sponsorblock-categories", &actions.to_variant())
+            .unwrap();
+    }
+
+    /// The active profile's filter, which is mirrored in settings so grids can watch it
+    pub fn content_filter(&self) -> ContentFilter {
+        ContentFilter::from_json(&self.settings.string("content-filter"))
+    }
+
+    pub fn set_content_filter(&self, filter: &ContentFilter) {
+        self.update_active_profile(|profile| profile.filter = filter.clone());
+        self.settings
+            .set_string("content-filter", &filter.to_json())
+            .unwrap();
+    }
+
+    fn lock_profiles(&self) -> MutexGuard<Profiles> {
+        self.profiles.lock().unwrap()
+    }
+
+    pub fn save_profiles(&self) {
+        self.profiles_changed.store(false, Ordering::Relaxed);
+        if self.profiles_unreadable {
+            return;
+        }
+        if let Err(error) = self.lock_profiles().save() {
+            log::warn!("Failed to save profiles: {}", error);
+        }
+    }
+
+    /// Writes the profiles if anything in them changed since they were last saved
+    pub fn save_changed_profiles(&self) {
+        if self.profiles_changed.load(Ordering::Relaxed) {
+            self.save_profiles();
+        }
+    }
+
+    pub fn profiles(&self) -> Vec<Profile> {
+        self.lock_profiles().profiles.clone()
+    }
+
+    pub fn active_profile_id(&self) -> String {
+        self.settings.string("active-profile").to_string()
+    }
+
+    pub fn active_profile(&self) -> Profile {
+        self.lock_profiles()
+            .get(&self.active_profile_id())
+            .cloned()
+            .expect("the active profile should exist")
+    }
+
+    /// Changes a profile. History and searches change with every video and search, so the
+    /// change is only written with the next autosave, see `save_changed_profiles`
+    pub fn update_profile(&self, id: &str, update: impl FnOnce(&mut Profile)) {
+        if let Some(profile) = self.lock_profiles().get_mut(id) {
+            update(profile);
+            self.profiles_changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn update_active_profile(&self, update: impl FnOnce(&mut Profile)) {
+        self.update_profile(&self.active_profile_id(), update);
+    }
+
+    pub fn add_profile(&self, name: &str) -> String {
+        let profile = Profile::new(name);
+        let id = profile.id.clone();
+        self.lock_profiles().profiles.push(profile);
+        self.save_profiles();
+        id
+    }
+
+    /// Removes a profile with everything in it, the active profile can't be removed
+    pub fn remove_profile(&self, id: &str) {
+        if id == self.active_profile_id() {
+            return;
+        }
+        self.lock_profiles().profiles.retain(|x| x.id != id);
+        self.save_profiles();
+    }
+
+    /// Stores the selected instance as the active profile's preferred one
+    pub fn remember_selected_instance(&self) {
+        let selected = self.invidious.selected_instance().map(|x| x.uri.clone());
+        self.update_active_profile(|profile| profile.instance = selected);
+    }
+
+    /// Makes another profile active. Its PIN has to be checked before calling this
+    pub fn switch_profile(&self, id: &str) {
+        if self.lock_profiles().get(id).is_none() {
+            return;
+        }
+        self.remember_selected_instance();
+        self.activate_profile(id);
+    }
+
+    fn activate_profile(&self, id: &str) {
+        let Some(profile) = self.lock_profiles().get(id).cloned() else {
+            return;
+        };
+        // The instance may have been removed since
+        if let Some(uri) = profile
+            .instance
+            .filter(|uri| self.invidious.instances().iter().any(|x| &x.uri == uri))
+        {
+            if let Err(error) = self.invidious.select_instance_by_name(&uri) {
+                log::warn!("Failed to select instance of profile: {}", error);
+            }
+        }
+        self.settings
+            .set_string("content-filter", &profile.filter.to_json())
+            .unwrap();
+        // Changed last, so anything watching it sees the whole profile
+        self.settings.set_string("active-profile", id).unwrap();
+    }
+
+    pub fn record_history(&self, video: &DetailedVideo) {
+        let entry = HistoryEntry {
+            video_id: video.id.clone(),
+            title: video.title.clone(),
+            author: video.author.clone(),
+            author_id: video.author_id.clone(),
+            watched: now(),
+        };
+        self.update_active_profile(|profile| profile.record_history(entry));
+    }
+
+    /// Watched videos, most recent first
+    pub fn watch_history(&self) -> Vec<HistoryEntry> {
+        self.active_profile().history
+    }
+
+    pub fn clear_watch_history(&self) {
+        self.update_active_profile(|profile| profile.history.clear());
+    }
+
+    pub fn search_history(&self) -> Vec<String> {
+        self.active_profile().searches
+    }
+
+    /// Remembers a search, unless recording searches is turned off
+    pub fn record_search(&self, query: &str) {
+        if self.settings.boolean("record-search-history") {
+            self.update_active_profile(|profile| profile.record_search(query));
+        }
+    }
+
+    pub fn remove_search(&self, query: &str) {
+        self.update_active_profile(|profile| profile.searches.retain(|x| x != query));
+    }
+
+    pub fn clear_search_history(&self) {
+        self.update_active_profile(|profile| profile.searches.clear());
+    }
+
+    pub fn subscriptions(&self) -> Vec<Subscription> {
+        self.active_profile().subscriptions
+    }
+
+    pub fn is_subscribed(&self, channel_id: &str) -> bool {
+        self.active_profile().is_subscribed(channel_id)
+    }
+
+    pub fn set_subscribed(&self, channel_id: &str, name: &str, subscribed: bool) {
+        self.update_active_profile(|profile| {
+            profile.subscriptions.retain(|x| x.id != channel_id);
+            if subscribed {
+                profile.subscriptions.push(Subscription {
+                    id: channel_id.to_string(),
+                    name: name.to_string(),
+                });
+            }
+        });
+    }
+
+    pub fn playlists(&self) -> Vec<LocalPlaylist> {
+        self.active_profile().playlists
+    }
+
+    pub fn create_playlist(&self, name: &str) -> String {
+        let playlist = LocalPlaylist::new(name);
+        let id = playlist.id.clone();
+        self.update_active_profile(|profile| profile.playlists.push(playlist));
+        id
+    }
+
+    pub fn remove_playlist(&self, id: &str) {
+        self.update_active_profile(|profile| profile.playlists.retain(|x| x.id != id));
+    }
+
+    /// Adds a video to the end of a playlist, unless it is in there already
+    pub fn add_to_playlist(&self, id: &str, video: &DetailedVideo) {
+        let entry = PlaylistVideo {
+            video_id: video.id.clone(),
+            title: video.title.clone(),
+            author: video.author.clone(),
+        };
+        self.update_active_profile(|profile| {
+            if let Some(playlist) = profile.playlists.iter_mut().find(|x| x.id == id) {
+                if !playlist.contains(&entry.video_id) {
+                    playlist.videos.push(entry);
+                }
+            }
+        });
+    }
+
+    pub fn remove_from_playlist(&self, id: &str, video_id: &str) {
+        self.update_active_profile(|profile| {
+            if let Some(playlist) = profile.playlists.iter_mut().find(|x| x.id == id) {
+                playlist.videos.retain(|x| x.video_id != video_id);
+            }
+        });
+    }
+
+    /// Playback speed last used for a channel's videos
+    pub fn channel_speed(&self, author_id: &str) -> f64 {
+        self.settings
+            .value("channel-speeds")
+            .get::<HashMap<String, f64>>()
+            .and_then(|speeds| speeds.get(author_id).copied())
+            .unwrap_or(1.0)
+    }
+
+    pub fn set_channel_speed(&self, author_id: &str, speed: f64) {
+        let mut speeds: HashMap<String, f64> = self
+            .settings
+            .value("channel-speeds")
+            .get()
+            .unwrap_or_default();
+        let changed = if speed == 1.0 {
+            speeds.remove(author_id).is_some()
+        } else {
+            speeds.insert(author_id.to_string(), speed) != Some(speed)
+        };
+        if changed {
+            self.settings
+                .set_value("channel-speeds", &speeds.to_variant())
+                .unwrap();
+        }
+    }
+}
+
+/// Whether changing `profile` needs its PIN, which the active profile's was already entered for
+fn is_protected(profile: &Profile, active: &str) -> bool {
+    profile.is_locked() && profile.id != active
 }
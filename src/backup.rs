@@ -0,0 +1,213 @@
+use gio::Settings;
+use gtk::{gio, glib};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::api::Instances;
+use crate::profiles::{now, Profile};
+
+/// Format written by this version of Pryvid, raised whenever it changes
+pub const BACKUP_VERSION: u64 = 1;
+// Keys stored in the backup in another form, or that only describe this installation
+const EXCLUDED_KEYS: [&str; 5] = [
+    "first-run",
+    "instances",
+    "selected",
+    "content-filter",
+    "active-profile",
+];
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("Backup can't be read")]
+    Invalid(#[from] serde_json::Error),
+    #[error("Not a Pryvid backup")]
+    NotBackup,
+    #[error("Backup was made by a newer version of Pryvid")]
+    TooNew,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreMode {
+    /// Adds what's missing and keeps current settings
+    Merge,
+    /// Makes everything exactly like it was when the backup was made
+    Replace,
+}
+
+/// Everything Pryvid keeps locally, in one file
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Backup {
+    pub version: u64,
+    // Seconds since the Unix epoch
+    pub created: u64,
+    pub instances: Instances,
+    pub selected: Option<String>,
+    // Values in GVariant text format, by key
+    pub settings: BTreeMap<String, String>,
+    pub profiles: Vec<Profile>,
+    pub active_profile: Option<String>,
+}
+
+impl Backup {
+    pub fn new(
+        instances: Instances,
+        selected: Option<String>,
+        settings: &Settings,
+        profiles: Vec<Profile>,
+        active_profile: String,
+    ) -> Self {
+        Backup {
+            version: BACKUP_VERSION,
+            created: now(),
+            instances,
+            selected,
+            settings: backed_up_keys(settings)
+                .into_iter()
+                .map(|key| (key.clone(), settings.value(&key).print(true).to_string()))
+                .collect(),
+            profiles,
+            active_profile: Some(active_profile),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, BackupError> {
+        let backup: Value = serde_json::from_str(json)?;
+        let version = backup
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or(BackupError::NotBackup)?;
+        if version > BACKUP_VERSION {
+            return Err(BackupError::TooNew);
+        }
+        Ok(serde_json::from_value(backup)?)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Writes the backed up settings, skipping values this version can't read
+    pub fn apply_settings(&self, settings: &Settings) {
+        let Some(schema) = settings.settings_schema() else {
+            return;
+        };
+        for key in backed_up_keys(settings) {
+            let Some(text) = self.settings.get(&key) else {
+                continue;
+            };
+            let value_type = schema.key(&key).value_type();
+            match glib::Variant::parse(Some(&*value_type), text) {
+                Ok(value) => {
+                    if let Err(error) = settings.set_value(&key, &value) {
+                        log::warn!("Failed to restore setting {}: {}", key, error);
+                    }
+                }
+                Err(error) => log::warn!("Ignoring backed up setting {}: {}", key, error),
+            }
+        }
+    }
+}
+
+fn backed_up_keys(settings: &Settings) -> Vec<String> {
+    settings
+        .settings_schema()
+        .map(|schema| schema.list_keys())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|key| key.to_string())
+        .filter(|key| !EXCLUDED_KEYS.contains(&key.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BACKUP: &str = r#"{
+        "version": 1,
+        "created": 1700000000,
+        "instances": [
+            {
+                "uri": "https://invidious.example.org",
+                "info": {
+                    "has_trending": true,
+                    "has_popular": null,
+                    "open_registrations": false
+                }
+            }
+        ],
+        "selected": "https://invidious.example.org",
+        "settings": { "autoplay": "true" },
+        "profiles": [],
+        "active_profile": null
+    }"#;
+
+    #[test]
+    fn reads_backups() {
+        let backup = Backup::from_json(BACKUP).unwrap();
+        assert_eq!(backup.version, BACKUP_VERSION);
+        assert_eq!(backup.instances.len(), 1);
+        assert_eq!(backup.instances[0].uri, "https://invidious.example.org");
+        assert_eq!(
+            backup.instances[0].info.read().unwrap().has_trending,
+            Some(true)
+        );
+        assert_eq!(
+            backup.selected.as_deref(),
+            Some("https://invidious.example.org")
+        );
+        assert_eq!(
+            backup.settings.get("autoplay").map(String::as_str),
+            Some("true")
+        );
+        assert!(backup.profiles.is_empty());
+    }
+
+    #[test]
+    fn fills_in_missing_fields() {
+        let backup = Backup::from_json(r#"{"version": 1}"#).unwrap();
+        assert!(backup.instances.is_empty());
+        assert_eq!(backup.active_profile, None);
+    }
+
+    #[test]
+    fn reads_the_current_version_again() {
+        let backup = Backup::from_json(BACKUP).unwrap();
+        let backup = Backup::from_json(&backup.to_json()).unwrap();
+        assert_eq!(backup.instances.len(), 1);
+        assert_eq!(backup.settings.len(), 1);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(matches!(
+            Backup::from_json(r#"{"name": "something else"}"#),
+            Err(BackupError::NotBackup)
+        ));
+        assert!(matches!(
+            Backup::from_json("not json"),
+            Err(BackupError::Invalid(_))
+        ));
+        // The `instances` setting isn't a backup
+        assert!(matches!(
+            Backup::from_json(r#"[{"uri": "https://invidious.example.org"}]"#),
+            Err(BackupError::NotBackup)
+        ));
+        assert!(matches!(
+            Backup::from_json(r#"{"version": 1, "instances": 5}"#),
+            Err(BackupError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        assert!(matches!(
+            Backup::from_json(r#"{"version": 99}"#),
+            Err(BackupError::TooNew)
+        ));
+    }
+}
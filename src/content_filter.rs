@@ -0,0 +1,197 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// Invidious doesn't mark Shorts, so they're guessed by length. Shorts can be up to three
+// minutes long, but most are a minute or less, and a higher limit would hide too many
+// ordinary videos
+const SHORTS_MAX_LENGTH: u32 = 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockedChannel {
+    pub id: String,
+    pub name: String,
+}
+
+/// Rules for hiding content from every grid, stored as JSON in settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentFilter {
+    pub blocked_channels: Vec<BlockedChannel>,
+    // Plain words match anywhere in a title, ignoring case. Keywords surrounded by
+    // slashes are regular expressions
+    pub keywords: Vec<String>,
+    // Seconds, 0 means no limit
+    pub min_length: u32,
+    pub max_length: u32,
+    pub hide_shorts: bool,
+    pub hide_live: bool,
+
+    #[serde(skip)]
+    patterns: Vec<Regex>,
+}
+
+impl ContentFilter {
+    pub fn from_json(json: &str) -> Self {
+        let mut filter: Self = serde_json::from_str(json).unwrap_or_else(|error| {
+            log::warn!(
+                "Failed to read content filter, nothing will be hidden: {}",
+                error
+            );
+            Self::default()
+        });
+        filter.compile();
+        filter
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    fn compile(&mut self) {
+        self.patterns = self
+            .keywords
+            .iter()
+            .filter_map(|keyword| {
+                let pattern = match keyword.strip_prefix('/').and_then(|x| x.strip_suffix('/')) {
+                    // An empty pattern would match every title
+                    Some(pattern) if pattern.trim().is_empty() => return None,
+                    None if keyword.trim().is_empty() => return None,
+                    Some(pattern) => format!("(?i){}", pattern),
+                    None => format!("(?i){}", regex::escape(keyword)),
+                };
+                Regex::new(&pattern)
+                    .map_err(|error| log::warn!("Ignoring keyword {:?}: {}", keyword, error))
+                    .ok()
+            })
+            .collect();
+    }
+
+    /// Whether a keyword would be used, so broken regular expressions can be pointed out
+    pub fn is_valid_keyword(keyword: &str) -> bool {
+        match keyword.strip_prefix('/').and_then(|x| x.strip_suffix('/')) {
+            Some(pattern) => !pattern.trim().is_empty() && Regex::new(pattern).is_ok(),
+            None => !keyword.trim().is_empty(),
+        }
+    }
+
+    pub fn add_keyword(&mut self, keyword: &str) {
+        if !self.keywords.iter().any(|x| x == keyword) {
+            self.keywords.push(keyword.to_string());
+            self.compile();
+        }
+    }
+
+    pub fn remove_keyword(&mut self, keyword: &str) {
+        self.keywords.retain(|x| x != keyword);
+        self.compile();
+    }
+
+    pub fn block_channel(&mut self, id: &str, name: &str) {
+        if !self.is_blocked(id) {
+            self.blocked_channels.push(BlockedChannel {
+                id: id.to_string(),
+                name: name.to_string(),
+            });
+        }
+    }
+
+    pub fn unblock_channel(&mut self, id: &str) {
+        self.blocked_channels.retain(|x| x.id != id);
+    }
+
+    /// Adds the channels and keywords of another filter, keeping this one's other rules
+    pub fn merge(&mut self, other: &ContentFilter) {
+        for channel in &other.blocked_channels {
+            self.block_channel(&channel.id, &channel.name);
+        }
+        for keyword in &other.keywords {
+            self.add_keyword(keyword);
+        }
+    }
+
+    pub fn is_blocked(&self, author_id: &str) -> bool {
+        self.blocked_channels.iter().any(|x| x.id == author_id)
+    }
+
+    fn matches_keyword(&self, title: &str) -> bool {
+        self.patterns.iter().any(|x| x.is_match(title))
+    }
+
+    /// `length` is in seconds, 0 when unknown
+    pub fn allows(&self, author_id: &str, title: &str, length: u32, live: bool) -> bool {
+        if self.is_blocked(author_id) || self.matches_keyword(title) {
+            return false;
+        }
+        if live {
+            return !self.hide_live;
+        }
+        // Without a length, there's nothing to compare against
+        if length == 0 {
+            return true;
+        }
+        !(self.hide_shorts && length <= SHORTS_MAX_LENGTH
+            || self.min_length > 0 && length < self.min_length
+            || self.max_length > 0 && length > self.max_length)
+    }
+
+    pub fn allows_playlist(&self, author_id: &str, title: &str) -> bool {
+        !self.is_blocked(author_id) && !self.matches_keyword(title)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter_with(keyword: &str) -> ContentFilter {
+        let mut filter = ContentFilter::default();
+        filter.add_keyword(keyword);
+        filter
+    }
+
+    #[test]
+    fn plain_keywords_match_anywhere_ignoring_case() {
+        let filter = filter_with("Unboxing");
+        assert!(!filter.allows("", "Big UNBOXING video", 0, false));
+        assert!(!filter.allows("", "unboxings", 0, false));
+        assert!(filter.allows("", "Boxing", 0, false));
+    }
+
+    #[test]
+    fn plain_keywords_are_not_patterns() {
+        let filter = filter_with("c++");
+        assert!(!filter.allows("", "Learn C++ fast", 0, false));
+        assert!(filter.allows("", "Learn C fast", 0, false));
+    }
+
+    #[test]
+    fn slashes_make_a_regular_expression() {
+        let filter = filter_with(r"/\bcat\b/");
+        assert!(!filter.allows("", "My Cat", 0, false));
+        assert!(filter.allows("", "concatenate", 0, false));
+        assert!(!filter.allows_playlist("", "cat videos"));
+    }
+
+    #[test]
+    fn ignores_broken_regular_expressions() {
+        assert!(!ContentFilter::is_valid_keyword("/(/"));
+        assert!(!ContentFilter::is_valid_keyword("  "));
+        assert!(ContentFilter::is_valid_keyword("(/"));
+        assert!(filter_with("/(/").allows("", "(", 0, false));
+    }
+
+    #[test]
+    fn ignores_empty_patterns() {
+        assert!(!ContentFilter::is_valid_keyword("//"));
+        assert!(!ContentFilter::is_valid_keyword("/  /"));
+        assert!(filter_with("//").allows("", "Anything", 0, false));
+        assert!(filter_with("/ /").allows("", "Any thing", 0, false));
+        assert!(filter_with("").allows("", "Anything", 0, false));
+    }
+
+    #[test]
+    fn keywords_survive_json() {
+        let filter = ContentFilter::from_json(&filter_with("/^live/").to_json());
+        assert!(!filter.allows("", "Live now", 0, false));
+    }
+}
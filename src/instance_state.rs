@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::api::Instances;
+
+/// Version of the `instances` setting's format. Version 1 is the plain list of instances it
+/// held before it had a version
+pub const STATE_VERSION: u64 = 2;
+
+#[derive(Debug, Error)]
+pub enum StateError {
+    #[error("Saved instances can't be read")]
+    Invalid(#[from] serde_json::Error),
+    #[error("Saved instances are from a newer version of Pryvid")]
+    TooNew,
+}
+
+/// Instances with their statistics and the selected one, as stored in the `instances` setting
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InstanceState {
+    pub version: u64,
+    pub instances: Instances,
+    pub selected: Option<String>,
+}
+
+impl InstanceState {
+    pub fn new(instances: Instances, selected: Option<String>) -> Self {
+        InstanceState {
+            version: STATE_VERSION,
+            instances,
+            selected,
+        }
+    }
+
+    /// `selected` is the separate setting the selected instance used to be kept in
+    pub fn from_json(json: &str, selected: &str) -> Result<Self, StateError> {
+        Ok(serde_json::from_value(migrate(
+            serde_json::from_str(json)?,
+            selected,
+        )?)?)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+/// Moves a plain list of instances and the separate selection into the current format
+fn migrate(state: Value, selected: &str) -> Result<Value, StateError> {
+    let version = match &state {
+        Value::Array(_) => 1,
+        value => value
+            .get("version")
+            .and_then(Value::as_u64)
+            .unwrap_or_default(),
+    };
+    match version {
+        1 => Ok(json!({
+            "version": 2,
+            "instances": state,
+            "selected": Some(selected).filter(|x| !x.is_empty()),
+        })),
+        version if version > STATE_VERSION => Err(StateError::TooNew),
+        // The current version, or a missing one that serde will point out
+        _ => Ok(state),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What `save_instances` wrote before the format had a version
+    const VERSION_1: &str = r#"[
+        {
+            "uri": "https://invidious.example.org",
+            "info": {
+                "has_trending": true,
+                "has_popular": false,
+                "open_registrations": true,
+                "health": {
+                    "search": { "successes": 4, "failures": 1 },
+                    "videos": { "successes": 2, "failures": 0 },
+                    "trending": { "successes": 0, "failures": 3 },
+                    "latencies": [120, 80]
+                }
+            }
+        },
+        {
+            "uri": "https://yewtu.example.net",
+            "info": {
+                "has_trending": null,
+                "has_popular": null,
+                "open_registrations": false
+            }
+        }
+    ]"#;
+
+    #[test]
+    fn migrates_version_1() {
+        let state = InstanceState::from_json(VERSION_1, "https://yewtu.example.net").unwrap();
+        assert_eq!(state.version, STATE_VERSION);
+        assert_eq!(state.selected.as_deref(), Some("https://yewtu.example.net"));
+        assert_eq!(state.instances.len(), 2);
+
+        let info = state.instances[0].info.read().unwrap();
+        assert_eq!(info.has_trending, Some(true));
+        assert_eq!(info.has_popular, Some(false));
+        assert!(info.open_registrations);
+        assert_eq!(info.health.search.successes, 4);
+        assert_eq!(info.health.trending.failures, 3);
+    }
+
+    #[test]
+    fn migrates_version_1_without_selection() {
+        let state = InstanceState::from_json(VERSION_1, "").unwrap();
+        assert_eq!(state.selected, None);
+    }
+
+    #[test]
+    fn migrates_empty_version_1() {
+        let state = InstanceState::from_json("[]", "").unwrap();
+        assert!(state.instances.is_empty());
+    }
+
+    #[test]
+    fn keeps_stats_when_saved_again() {
+        let state = InstanceState::from_json(VERSION_1, "").unwrap();
+        let state = InstanceState::from_json(&state.to_json(), "ignored").unwrap();
+        assert_eq!(state.selected, None);
+        let info = state.instances[0].info.read().unwrap();
+        assert_eq!(info.health.videos.successes, 2);
+    }
+
+    #[test]
+    fn rejects_corrupt_state() {
+        assert!(matches!(
+            InstanceState::from_json(r#"[{"uri": "#, ""),
+            Err(StateError::Invalid(_))
+        ));
+        assert!(matches!(
+            InstanceState::from_json(r#"{"version": 2, "instances": 5}"#, ""),
+            Err(StateError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        assert!(matches!(
+            InstanceState::from_json(r#"{"version": 99, "instances": []}"#, ""),
+            Err(StateError::TooNew)
+        ));
+    }
+}
@@ -1,7 +1,14 @@
 mod api;
 mod application;
 mod appmodel;
+mod backup;
 mod config;
+mod content_filter;
+mod instance_state;
+mod markup;
+mod player;
+mod profiles;
+mod radio;
 mod utils;
 mod widgets;
 
@@ -20,6 +27,10 @@ fn main() -> glib::ExitCode {
         .expect("Unable to set the text domain encoding");
     textdomain(GETTEXT_PACKAGE).expect("Unable to switch to the text domain");
 
+    // Set up GStreamer, with the sink that draws video into GTK
+    gst::init().expect("Unable to initialize GStreamer");
+    gstgtk4::plugin_register_static().expect("Unable to register the GTK 4 sink");
+
     // Load resources
     for resource in ["ui", "resources"] {
         let resources =
@@ -0,0 +1,347 @@
+use gtk::glib;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+lazy_static! {
+    static ref HREF_REGEX: Regex =
+        Regex::new(r#"(?i)\bhref\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap();
+    static ref ENTITY_REGEX: Regex =
+        Regex::new(r"&(#[0-9]{1,7}|#[xX][0-9a-fA-F]{1,6}|[a-zA-Z]+);").unwrap();
+    static ref TIME_PARAM_REGEX: Regex =
+        Regex::new(r"^(?:(\d+)h)?(?:(\d+)m)?(?:(\d+)s?)?$").unwrap();
+    static ref HASHTAG_REGEX: Regex = Regex::new(r"(^|\s)#([\p{L}\p{N}_]+)").unwrap();
+}
+
+const YOUTUBE_HOSTS: [&str; 6] = [
+    "youtube.com",
+    "www.youtube.com",
+    "m.youtube.com",
+    "music.youtube.com",
+    "youtube-nocookie.com",
+    "www.youtube-nocookie.com",
+];
+
+/// Where a link found in a description should take the user
+#[derive(Debug, Clone, PartialEq)]
+pub enum Link {
+    Video { id: String, start: Option<u32> },
+    Channel(String),
+    Playlist(String),
+    Hashtag(String),
+    Search(String),
+    // A YouTube page we can't identify on our own, like a channel handle
+    Resolve(String),
+    External(String),
+}
+
+/// Converts the HTML descriptions returned by Invidious into markup that a GtkLabel accepts.
+/// Only a handful of formatting tags and links are kept, everything else is stripped.
+pub fn html_to_pango(html: &str) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut open: Vec<&'static str> = Vec::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        push_text(&mut output, &rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+        push_tag(&mut output, &mut open, &rest[1..end]);
+        rest = &rest[end + 1..];
+    }
+    push_text(&mut output, rest);
+
+    while let Some(tag) = open.pop() {
+        let _ = write!(output, "</{}>", tag);
+    }
+    output
+}
+
+/// Escapes plain text like a title for a GtkLabel, turning its hashtags into links
+pub fn link_hashtags(text: &str) -> String {
+    // Escaping first is fine, the # of an escaped character always follows an &
+    let escaped = glib::markup_escape_text(text);
+    HASHTAG_REGEX
+        .replace_all(&escaped, |captures: &Captures| {
+            format!(
+                "{}<a href=\"/hashtag/{tag}\">#{tag}</a>",
+                &captures[1],
+                tag = &captures[2]
+            )
+        })
+        .into_owned()
+}
+
+/// Links in `html` that lead away from YouTube, in order and without duplicates
+pub fn external_links(html: &str) -> Vec<String> {
+    let mut links: Vec<String> = Vec::new();
+    for href in HREF_REGEX
+        .captures_iter(html)
+        .filter_map(|x| x.iter().skip(1).flatten().next())
+        .map(|x| decode_entities(x.as_str()))
+        .filter(|x| is_safe_link(x))
+    {
+        if let Link::External(uri) = parse_link(&href) {
+            if !links.contains(&uri) {
+                links.push(uri);
+            }
+        }
+    }
+    links
+}
+
+fn push_text(output: &mut String, text: &str) {
+    output.push_str(&glib::markup_escape_text(&decode_entities(text)));
+}
+
+fn push_tag(output: &mut String, open: &mut Vec<&'static str>, tag: &str) {
+    let closing = tag.starts_with('/');
+    let tag = tag.trim_start_matches('/').trim_end_matches('/').trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let name = tag[..name_end].to_ascii_lowercase();
+
+    if name == "br" {
+        output.push('\n');
+        return;
+    }
+    let pango = match name.as_str() {
+        "a" => "a",
+        "b" | "strong" => "b",
+        "i" | "em" => "i",
+        "u" => "u",
+        "s" | "del" | "strike" => "s",
+        "code" => "tt",
+        _ => return,
+    };
+
+    if closing {
+        // Only close tags we actually opened so the markup always nests properly
+        if let Some(index) = open.iter().rposition(|x| *x == pango) {
+            for tag in open.drain(index..).rev() {
+                let _ = write!(output, "</{}>", tag);
+            }
+        }
+    } else if pango == "a" {
+        // Links can't be nested, and unsafe ones are rendered as plain text
+        if open.contains(&"a") {
+            return;
+        }
+        if let Some(href) = HREF_REGEX
+            .captures(&tag[name_end..])
+            .and_then(|x| x.iter().skip(1).flatten().next())
+            .map(|x| decode_entities(x.as_str()))
+            .filter(|x| is_safe_link(x))
+        {
+            let _ = write!(output, "<a href=\"{}\">", glib::markup_escape_text(&href));
+            open.push("a");
+        }
+    } else {
+        let _ = write!(output, "<{}>", pango);
+        open.push(pango);
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    ENTITY_REGEX
+        .replace_all(text, |captures: &Captures| {
+            let entity = &captures[1];
+            let decoded = if let Some(hex) = entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+            {
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+            } else if let Some(decimal) = entity.strip_prefix('#') {
+                decimal.parse().ok().and_then(char::from_u32)
+            } else {
+                match entity {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some('\u{a0}'),
+                    _ => None,
+                }
+            };
+            decoded
+                .map(String::from)
+                .unwrap_or_else(|| captures[0].to_string())
+        })
+        .into_owned()
+}
+
+fn is_safe_link(href: &str) -> bool {
+    href.starts_with("https://")
+        || href.starts_with("http://")
+        || (href.starts_with('/') && !href.starts_with("//"))
+}
+
+fn decode_param(param: &str) -> String {
+    let param = param.replace('+', " ");
+    urlencoding::decode(&param)
+        .map(|x| x.into_owned())
+        .unwrap_or(param)
+}
+
+/// Parses YouTube-style `t` parameters like `83`, `83s` or `1h2m3s`
+fn parse_time_param(time: &str) -> Option<u32> {
+    let captures = TIME_PARAM_REGEX.captures(time)?;
+    if captures.iter().skip(1).all(|x| x.is_none()) {
+        return None;
+    }
+    let part = |index| {
+        captures
+            .get(index)
+            .and_then(|x| x.as_str().parse::<u32>().ok())
+            .unwrap_or(0)
+    };
+    Some(part(1) * 3600 + part(2) * 60 + part(3))
+}
+
+pub fn parse_link(uri: &str) -> Link {
+    // Relative links point at the instance, which mirrors YouTube's URL layout
+    let absolute = if uri.starts_with('/') {
+        format!("https://www.youtube.com{}", uri)
+    } else {
+        uri.to_string()
+    };
+    let Ok(parsed) = glib::Uri::parse(&absolute, glib::UriFlags::NONE) else {
+        return Link::External(uri.to_string());
+    };
+    let host = parsed.host().map(|x| x.to_lowercase()).unwrap_or_default();
+    let path = parsed.path().to_string();
+    let params: HashMap<String, String> = parsed
+        .query()
+        .map(|query| {
+            query
+                .split('&')
+                .map(|pair| {
+                    let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    (decode_param(key), decode_param(value))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let start = params.get("t").and_then(|x| parse_time_param(x));
+    let segments: Vec<&str> = path.split('/').filter(|x| !x.is_empty()).collect();
+
+    if host == "youtu.be" {
+        return match segments.first() {
+            Some(id) => Link::Video {
+                id: id.to_string(),
+                start,
+            },
+            None => Link::External(uri.to_string()),
+        };
+    }
+    if !YOUTUBE_HOSTS.contains(&host.as_str()) {
+        return Link::External(uri.to_string());
+    }
+
+    match segments.as_slice() {
+        ["watch"] => match params.get("v") {
+            Some(id) => Link::Video {
+                id: id.clone(),
+                start,
+            },
+            None => Link::External(uri.to_string()),
+        },
+        ["shorts" | "live" | "embed", id] => Link::Video {
+            id: id.to_string(),
+            start,
+        },
+        ["channel", id, ..] => Link::Channel(id.to_string()),
+        ["playlist"] => match params.get("list") {
+            Some(id) => Link::Playlist(id.clone()),
+            None => Link::External(uri.to_string()),
+        },
+        ["hashtag", tag] => Link::Hashtag(decode_param(tag)),
+        ["results" | "search"] => match params.get("search_query").or_else(|| params.get("q")) {
+            Some(query) => Link::Search(query.clone()),
+            None => Link::External(uri.to_string()),
+        },
+        ["redirect"] => match params.get("q") {
+            Some(target) if is_safe_link(target) => Link::External(target.clone()),
+            _ => Link::External(uri.to_string()),
+        },
+        _ => Link::Resolve(absolute),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_entities() {
+        assert_eq!(html_to_pango("&#x41;&#66;&nbsp;"), "AB\u{a0}");
+        // Decoded text is escaped again for Pango
+        assert_eq!(
+            html_to_pango("Tom &amp; Jerry &lt;3"),
+            "Tom &amp; Jerry &lt;3"
+        );
+        assert_eq!(html_to_pango("&bogus;"), "&amp;bogus;");
+    }
+
+    #[test]
+    fn keeps_formatting_and_strips_other_tags() {
+        assert_eq!(
+            html_to_pango("<div><strong>Bold</strong><br><code>x</code></div>"),
+            "<b>Bold</b>\n<tt>x</tt>"
+        );
+    }
+
+    #[test]
+    fn balances_tags() {
+        assert_eq!(
+            html_to_pango("<b>bold <i>both</b> plain</i>"),
+            "<b>bold <i>both</i></b> plain"
+        );
+        assert_eq!(html_to_pango("<b>open"), "<b>open</b>");
+        assert_eq!(html_to_pango("closed</u>"), "closed");
+    }
+
+    #[test]
+    fn rejects_unsafe_links() {
+        assert_eq!(html_to_pango(r#"<a href="javascript:alert(1)">x</a>"#), "x");
+        assert_eq!(html_to_pango(r#"<a href="//example.com">x</a>"#), "x");
+        assert_eq!(
+            html_to_pango(r#"<a href="https://example.com/?a=1&amp;b=2">x</a>"#),
+            r#"<a href="https://example.com/?a=1&amp;b=2">x</a>"#
+        );
+        assert_eq!(
+            html_to_pango("<a href='/watch?v=abc'>x</a>"),
+            r#"<a href="/watch?v=abc">x</a>"#
+        );
+    }
+
+    #[test]
+    fn safe_links() {
+        assert!(is_safe_link("https://example.com"));
+        assert!(is_safe_link("http://example.com"));
+        assert!(is_safe_link("/channel/abc"));
+        assert!(!is_safe_link("//example.com"));
+        assert!(!is_safe_link("javascript:alert(1)"));
+        assert!(!is_safe_link("data:text/html,x"));
+        assert!(!is_safe_link("file:///etc/passwd"));
+    }
+
+    #[test]
+    fn finds_external_links() {
+        let html = concat!(
+            r#"<a href="https://www.youtube.com/watch?v=abc">video</a> "#,
+            r#"<a href="https://example.com">site</a> "#,
+            r#"<a href="/redirect?q=https%3A%2F%2Fexample.org%2F">redirect</a> "#,
+            r#"<a href='https://example.com'>again</a> "#,
+            r#"<a href="javascript:alert(1)">bad</a>"#,
+        );
+        assert_eq!(
+            external_links(html),
+            vec!["https://example.com", "https://example.org/"]
+        );
+        assert!(external_links("No links at all").is_empty());
+    }
+}
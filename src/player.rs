@@ -0,0 +1,335 @@
+use glib::{Object, Properties, SendWeakRef};
+use gst::prelude::*;
+use gst_play::{Play, PlayMediaInfo, PlaySignalAdapter};
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{gdk, gio, glib};
+use std::cell::{Cell, OnceCell, RefCell};
+
+use crate::api;
+
+/// Playback rates offered in the speed menus
+pub const SPEEDS: [f64; 10] = [0.25, 0.5, 0.75, 1.0, 1.25, 1.5, 1.75, 2.0, 2.5, 3.0];
+
+/// Position of the closest offered speed to `rate`
+pub fn speed_index(rate: f64) -> usize {
+    SPEEDS
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| (*a - rate).abs().total_cmp(&(*b - rate).abs()))
+        .map(|(index, _)| index)
+        .unwrap_or_default()
+}
+
+/// Whether `element` fetches what it plays over HTTP
+fn is_http_source(element: &gst::Element) -> bool {
+    element
+        .dynamic_cast_ref::<gst::URIHandler>()
+        .is_some_and(|handler| {
+            handler.uri_type() == gst::URIType::Src
+                && handler
+                    .protocols()
+                    .iter()
+                    .any(|protocol| matches!(protocol.as_str(), "http" | "https"))
+        })
+}
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Debug, Properties)]
+    #[properties(wrapper_type = super::PlayerStream)]
+    pub struct PlayerStream {
+        #[property(get, set = Self::set_rate, minimum = 0.25, maximum = 3.0, default = 1.0)]
+        rate: Cell<f64>,
+
+        pub subtitle_uri: RefCell<Option<String>>,
+        pub play: OnceCell<Play>,
+        pub adapter: OnceCell<PlaySignalAdapter>,
+        pub paintable: OnceCell<gdk::Paintable>,
+    }
+
+    impl Default for PlayerStream {
+        fn default() -> Self {
+            Self {
+                rate: Cell::new(1.0),
+                subtitle_uri: RefCell::new(None),
+                play: OnceCell::new(),
+                adapter: OnceCell::new(),
+                paintable: OnceCell::new(),
+            }
+        }
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for PlayerStream {
+        const NAME: &'static str = "PlayerStream";
+        type Type = super::PlayerStream;
+        type ParentType = gtk::MediaStream;
+        type Interfaces = (gdk::Paintable,);
+    }
+
+    impl ObjectImpl for PlayerStream {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.obj().setup_player();
+        }
+
+        fn dispose(&self) {
+            if let Some(play) = self.play.get() {
+                play.stop();
+            }
+        }
+
+        fn properties() -> &'static [glib::ParamSpec] {
+            Self::derived_properties()
+        }
+
+        fn set_property(&self, id: usize, value: &glib::Value, pspec: &glib::ParamSpec) {
+            self.derived_set_property(id, value, pspec)
+        }
+
+        fn property(&self, id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+            self.derived_property(id, pspec)
+        }
+    }
+
+    impl MediaStreamImpl for PlayerStream {
+        fn play(&self) -> bool {
+            self.play.get().unwrap().play();
+            true
+        }
+
+        fn pause(&self) {
+            self.play.get().unwrap().pause();
+        }
+
+        fn seek(&self, timestamp: i64) {
+            self.play
+                .get()
+                .unwrap()
+                .seek(gst::ClockTime::from_useconds(timestamp.max(0) as u64));
+        }
+
+        fn update_audio(&self, muted: bool, volume: f64) {
+            let play = self.play.get().unwrap();
+            play.set_mute(muted);
+            play.set_volume(volume);
+        }
+    }
+
+    impl PaintableImpl for PlayerStream {
+        fn current_image(&self) -> gdk::Paintable {
+            self.paintable.get().unwrap().current_image()
+        }
+
+        fn flags(&self) -> gdk::PaintableFlags {
+            self.paintable.get().unwrap().flags()
+        }
+
+        fn intrinsic_width(&self) -> i32 {
+            self.paintable.get().unwrap().intrinsic_width()
+        }
+
+        fn intrinsic_height(&self) -> i32 {
+            self.paintable.get().unwrap().intrinsic_height()
+        }
+
+        fn intrinsic_aspect_ratio(&self) -> f64 {
+            self.paintable.get().unwrap().intrinsic_aspect_ratio()
+        }
+
+        fn snapshot(&self, snapshot: &gdk::Snapshot, width: f64, height: f64) {
+            self.paintable
+                .get()
+                .unwrap()
+                .snapshot(snapshot, width, height);
+        }
+    }
+
+    impl PlayerStream {
+        fn set_rate(&self, rate: f64) {
+            self.rate.set(rate);
+            if let Some(play) = self.play.get() {
+                play.set_rate(rate);
+            }
+        }
+    }
+}
+
+// A media stream played with GStreamer directly, since `gtk::MediaFile` can't change the
+// playback rate. Video frames come from `gtk4paintablesink` and are drawn as the stream's own
+// paintable, so it works anywhere a `gtk::MediaFile` did
+glib::wrapper! {
+    pub struct PlayerStream(ObjectSubclass<imp::PlayerStream>)
+        @extends gtk::MediaStream,
+        @implements gdk::Paintable;
+}
+
+impl PlayerStream {
+    pub fn for_uri(uri: &str) -> Self {
+        let obj: Self = Object::builder().build();
+        // The proxy can't be used, so the stream isn't loaded instead of going out directly
+        if api::requests_blocked() {
+            obj.gerror(glib::Error::new(
+                gio::IOErrorEnum::PermissionDenied,
+                &api::Error::ProxyUnavailable.to_string(),
+            ));
+            return obj;
+        }
+        let play = obj.imp().play.get().unwrap();
+        play.set_uri(Some(uri));
+        // Pausing prerolls the pipeline, which is what prepares the stream
+        play.pause();
+        obj
+    }
+
+    pub fn subtitle_uri(&self) -> Option<String> {
+        self.imp().subtitle_uri.borrow().clone()
+    }
+
+    /// Shows subtitles from a WebVTT file, or hides them
+    pub fn set_subtitle_uri(&self, uri: Option<&str>) {
+        let play = self.imp().play.get().unwrap();
+        if let Some(uri) = uri {
+            play.set_subtitle_uri(Some(uri));
+        }
+        play.set_subtitle_track_enabled(uri.is_some());
+        self.imp().subtitle_uri.replace(uri.map(str::to_string));
+    }
+
+    /// Moves a single frame forwards or backwards while paused. `frame` is how long a frame
+    /// lasts in microseconds
+    pub fn step_frame(&self, forward: bool, frame: i64) {
+        let play = self.imp().play.get().unwrap();
+        let pipeline = play.pipeline();
+        let position = play
+            .position()
+            .map(|x| x.useconds() as i64)
+            .unwrap_or_else(|| self.timestamp());
+
+        let target = if forward {
+            // Steps exactly one decoded frame, a seek would jump to the closest keyframe
+            let step = gst::event::Step::new(gst::format::Buffers::from_u64(1), 1.0, true, false);
+            if !pipeline.send_event(step) {
+                log::warn!("Failed to step forwards a frame");
+                return;
+            }
+            position + frame
+        } else {
+            // GStreamer can't step backwards, so this seeks to exactly the previous frame
+            let target = (position - frame).max(0);
+            if let Err(error) = pipeline.seek_simple(
+                gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+                gst::ClockTime::from_useconds(target as u64),
+            ) {
+                log::warn!("Failed to step backwards a frame: {}", error);
+                return;
+            }
+            target
+        };
+        // Neither goes through `Play`, so it doesn't report the new position
+        self.update(target);
+    }
+
+    fn setup_player(&self) {
+        let imp = self.imp();
+
+        let sink = gst::ElementFactory::make("gtk4paintablesink")
+            .build()
+            .expect("gtk4paintablesink should be registered in main");
+        let paintable = sink.property::<gdk::Paintable>("paintable");
+        paintable.connect_invalidate_contents(
+            glib::clone!(@weak self as obj => move |_| obj.invalidate_contents()),
+        );
+        paintable.connect_invalidate_size(
+            glib::clone!(@weak self as obj => move |_| obj.invalidate_size()),
+        );
+
+        let play = Play::new(None::<gst_play::PlayVideoRenderer>);
+        let pipeline = play.pipeline();
+        pipeline.set_property("video-sink", &sink);
+        // Keeps voices at their normal pitch when the rate changes
+        if let Ok(scaletempo) = gst::ElementFactory::make("scaletempo").build() {
+            pipeline.set_property("audio-filter", &scaletempo);
+        }
+        // Streams go through the same proxy as every other request. It's set on each HTTP
+        // source, including the ones adaptive streams create, as they're added. Sources that
+        // would connect directly are stopped before they start instead
+        if let Some(bin) = pipeline.downcast_ref::<gst::Bin>() {
+            bin.connect_deep_element_added(|_, _, element| {
+                if !is_http_source(element) {
+                    return;
+                }
+                let error = match api::media_route() {
+                    api::MediaRoute::Direct => return,
+                    api::MediaRoute::Proxy(proxy) if element.find_property("proxy").is_some() => {
+                        element.set_property("proxy", proxy);
+                        return;
+                    }
+                    api::MediaRoute::Proxy(_) => "This stream can't be sent through the proxy",
+                    api::MediaRoute::Blocked => "The proxy can't be used, so nothing is sent",
+                };
+                log::warn!("Refusing to start {}: {}", element.name(), error);
+                element.set_locked_state(true);
+                element.post_error_message(gst::error_msg!(
+                    gst::ResourceError::OpenRead,
+                    ["{}", error]
+                ));
+            });
+        }
+
+        // The adapter emits on the main context, so the weak reference is only ever
+        // upgraded on the thread that owns the stream
+        let adapter = PlaySignalAdapter::new(&play);
+        let weak = SendWeakRef::from(self.downgrade());
+        adapter.connect_media_info_updated(glib::clone!(@strong weak => move |_, info| {
+            if let Some(obj) = weak.upgrade() {
+                obj.update_media_info(info);
+            }
+        }));
+        adapter.connect_position_updated(glib::clone!(@strong weak => move |_, position| {
+            if let (Some(obj), Some(position)) = (weak.upgrade(), position) {
+                if obj.is_prepared() {
+                    obj.update(position.useconds() as i64);
+                }
+            }
+        }));
+        adapter.connect_seek_done(glib::clone!(@strong weak => move |_, position| {
+            if let Some(obj) = weak.upgrade() {
+                obj.seek_success();
+                obj.update(position.useconds() as i64);
+            }
+        }));
+        adapter.connect_end_of_stream(glib::clone!(@strong weak => move |_| {
+            if let Some(obj) = weak.upgrade() {
+                obj.stream_ended();
+            }
+        }));
+        adapter.connect_error(move |_, error, _| {
+            if let Some(obj) = weak.upgrade() {
+                obj.gerror(error.clone());
+            }
+        });
+
+        imp.paintable.set(paintable).unwrap();
+        imp.play.set(play).unwrap();
+        imp.adapter.set(adapter).unwrap();
+    }
+
+    fn update_media_info(&self, info: &PlayMediaInfo) {
+        if self.is_prepared() {
+            return;
+        }
+        self.stream_prepared(
+            info.number_of_audio_streams() > 0,
+            info.number_of_video_streams() > 0,
+            info.is_seekable(),
+            info.duration()
+                .map(|x| x.useconds() as i64)
+                .unwrap_or_default(),
+        );
+        self.imp().play.get().unwrap().set_rate(self.rate());
+    }
+}
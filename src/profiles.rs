@@ -0,0 +1,302 @@
+use gtk::glib;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::content_filter::ContentFilter;
+
+// Older entries are dropped so the profiles file doesn't grow forever
+const HISTORY_LIMIT: usize = 1000;
+const SEARCH_HISTORY_LIMIT: usize = 100;
+
+#[derive(Debug, Error)]
+pub enum ProfilesError {
+    #[error("Profiles can't be read: {0}")]
+    Io(#[from] io::Error),
+    #[error("Profiles are invalid: {0}")]
+    Invalid(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub video_id: String,
+    pub title: String,
+    pub author: String,
+    pub author_id: String,
+    // Seconds since the Unix epoch
+    pub watched: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlaylistVideo {
+    pub video_id: String,
+    pub title: String,
+    pub author: String,
+}
+
+/// Videos saved under a name, kept only in the profile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalPlaylist {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub videos: Vec<PlaylistVideo>,
+}
+
+impl LocalPlaylist {
+    pub fn new(name: &str) -> Self {
+        LocalPlaylist {
+            id: random_id(),
+            name: name.to_string(),
+            videos: Vec::new(),
+        }
+    }
+
+    pub fn contains(&self, video_id: &str) -> bool {
+        self.videos.iter().any(|x| x.video_id == video_id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Pin {
+    salt: String,
+    hash: String,
+}
+
+impl Pin {
+    fn new(pin: &str) -> Self {
+        let salt: [u8; 16] = rand::thread_rng().gen();
+        let salt = to_hex(&salt);
+        Pin {
+            hash: hash_pin(&salt, pin),
+            salt,
+        }
+    }
+
+    fn matches(&self, pin: &str) -> bool {
+        hash_pin(&self.salt, pin) == self.hash
+    }
+}
+
+/// Everything that belongs to one person using the app
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pin: Option<Pin>,
+    #[serde(default)]
+    pub subscriptions: Vec<Subscription>,
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
+    #[serde(default)]
+    pub playlists: Vec<LocalPlaylist>,
+    // Most recent first
+    #[serde(default)]
+    pub searches: Vec<String>,
+    #[serde(default)]
+    pub filter: ContentFilter,
+    // URI of the instance to select when switching to this profile
+    #[serde(default)]
+    pub instance: Option<String>,
+}
+
+impl Profile {
+    pub fn new(name: &str) -> Self {
+        Profile {
+            id: random_id(),
+            name: name.to_string(),
+            pin: None,
+            subscriptions: Vec::new(),
+            history: Vec::new(),
+            playlists: Vec::new(),
+            searches: Vec::new(),
+            filter: ContentFilter::default(),
+            instance: None,
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.pin.is_some()
+    }
+
+    /// Sets or removes the PIN needed to switch to this profile
+    pub fn set_pin(&mut self, pin: Option<&str>) {
+        self.pin = pin.map(Pin::new);
+    }
+
+    pub fn unlocks_with(&self, pin: &str) -> bool {
+        self.pin.as_ref().map_or(true, |x| x.matches(pin))
+    }
+
+    pub fn is_subscribed(&self, channel_id: &str) -> bool {
+        self.subscriptions.iter().any(|x| x.id == channel_id)
+    }
+
+    pub fn record_history(&mut self, entry: HistoryEntry) {
+        self.history.retain(|x| x.video_id != entry.video_id);
+        self.history.insert(0, entry);
+        self.history.truncate(HISTORY_LIMIT);
+    }
+
+    pub fn record_search(&mut self, query: &str) {
+        self.searches.retain(|x| x != query);
+        self.searches.insert(0, query.to_string());
+        self.searches.truncate(SEARCH_HISTORY_LIMIT);
+    }
+
+    /// Adds everything from another copy of this profile that this one doesn't have yet.
+    /// The name, PIN and instance of this one are kept
+    pub fn merge(&mut self, other: Profile) {
+        for subscription in other.subscriptions {
+            if !self.is_subscribed(&subscription.id) {
+                self.subscriptions.push(subscription);
+            }
+        }
+
+        for entry in other.history {
+            if !self.history.iter().any(|x| x.video_id == entry.video_id) {
+                self.history.push(entry);
+            }
+        }
+        self.history.sort_by(|a, b| b.watched.cmp(&a.watched));
+        self.history.truncate(HISTORY_LIMIT);
+
+        for playlist in other.playlists {
+            match self.playlists.iter_mut().find(|x| x.id == playlist.id) {
+                Some(existing) => {
+                    for video in playlist.videos {
+                        if !existing.contains(&video.video_id) {
+                            existing.videos.push(video);
+                        }
+                    }
+                }
+                None => self.playlists.push(playlist),
+            }
+        }
+
+        for query in other.searches {
+            if !self.searches.contains(&query) {
+                self.searches.push(query);
+            }
+        }
+        self.searches.truncate(SEARCH_HISTORY_LIMIT);
+
+        self.filter.merge(&other.filter);
+    }
+}
+
+/// All profiles, kept in a JSON file in the user's data directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profiles {
+    pub profiles: Vec<Profile>,
+}
+
+impl Profiles {
+    fn path() -> PathBuf {
+        glib::user_data_dir().join("pryvid").join("profiles.json")
+    }
+
+    /// Reads the saved profiles, `None` if there are none yet
+    pub fn load() -> Result<Option<Self>, ProfilesError> {
+        let json = match fs::read_to_string(Self::path()) {
+            Ok(json) => json,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        let profiles = serde_json::from_str::<Self>(&json)?;
+        Ok(Some(profiles).filter(|x| !x.profiles.is_empty()))
+    }
+
+    /// Copies a file that failed to load next to it, so it can still be recovered by hand
+    pub fn keep_broken() -> io::Result<PathBuf> {
+        let path = Self::path().with_file_name("profiles.broken.json");
+        fs::copy(Self::path(), &path)?;
+        Ok(path)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|x| x.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut Profile> {
+        self.profiles.iter_mut().find(|x| x.id == id)
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default()
+}
+
+fn hash_pin(salt: &str, pin: &str) -> String {
+    to_hex(&Sha256::digest(format!("{}{}", salt, pin).as_bytes()))
+}
+
+fn random_id() -> String {
+    let id: [u8; 8] = rand::thread_rng().gen();
+    to_hex(&id)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(id: &str) -> PlaylistVideo {
+        PlaylistVideo {
+            video_id: id.to_string(),
+            title: format!("Video {}", id),
+            author: "Someone".to_string(),
+        }
+    }
+
+    #[test]
+    fn merges_playlists() {
+        let mut profile = Profile::new("Here");
+        let mut shared = LocalPlaylist::new("Music");
+        shared.videos.push(video("a"));
+        profile.playlists.push(shared);
+
+        let mut other = profile.clone();
+        other.playlists[0].videos.push(video("b"));
+        other.playlists[0].videos.push(video("a"));
+        other.playlists.push(LocalPlaylist::new("Later"));
+        profile.merge(other);
+
+        assert_eq!(profile.playlists.len(), 2);
+        assert_eq!(profile.playlists[0].videos, vec![video("a"), video("b")]);
+        assert_eq!(profile.playlists[1].name, "Later");
+    }
+
+    #[test]
+    fn reads_profiles_without_playlists() {
+        let profiles: Profiles =
+            serde_json::from_str(r#"{"profiles": [{"id": "1", "name": "Old"}]}"#).unwrap();
+        assert!(profiles.profiles[0].playlists.is_empty());
+    }
+}
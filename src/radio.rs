@@ -0,0 +1,50 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::api::{Mix, Video};
+
+// Another mix is fetched once fewer videos than this are left
+const REFILL_THRESHOLD: usize = 3;
+
+/// Videos to play after each other, kept going by mixes of the videos that were played
+#[derive(Debug, Default)]
+pub struct Radio {
+    // Video that is playing as part of the radio, other videos don't advance it
+    current: String,
+    queue: VecDeque<Video>,
+    // Every video the radio has played or queued, so mixes don't repeat each other
+    seen: HashSet<String>,
+}
+
+impl Radio {
+    pub fn new(video_id: &str) -> Self {
+        Radio {
+            current: video_id.to_string(),
+            queue: VecDeque::new(),
+            seen: HashSet::from([video_id.to_string()]),
+        }
+    }
+
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    /// Queues the videos of `mix` that haven't come up yet
+    pub fn extend(&mut self, mix: Mix) {
+        for video in mix.videos {
+            if self.seen.insert(video.id.clone()) {
+                self.queue.push_back(video);
+            }
+        }
+    }
+
+    /// Moves on to the next video, if there is one
+    pub fn advance(&mut self) -> Option<Video> {
+        let video = self.queue.pop_front()?;
+        self.current = video.id.clone();
+        Some(video)
+    }
+
+    pub fn needs_more(&self) -> bool {
+        self.queue.len() < REFILL_THRESHOLD
+    }
+}
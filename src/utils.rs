@@ -9,3 +9,14 @@ pub fn format_number_magnitude(number: u64) -> String {
         format!("{}B", number / 1000000000)
     }
 }
+
+pub fn format_duration(seconds: u32) -> String {
+    let hours = seconds / 3600;
+    let minutes = (seconds / 60) % 60;
+    let seconds = seconds % 60;
+    if hours > 0 {
+        format!("{:0>2}:{:0>2}:{:0>2}", hours, minutes, seconds)
+    } else {
+        format!("{:0>2}:{:0>2}", minutes, seconds)
+    }
+}
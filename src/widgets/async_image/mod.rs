@@ -7,6 +7,8 @@ use gtk::{gdk_pixbuf::PixbufLoader, CompositeTemplate};
 use isahc::prelude::*;
 use std::cell::{Cell, RefCell};
 
+use crate::api;
+
 mod imp {
 
     use gtk::gdk_pixbuf::InterpType;
@@ -26,6 +28,11 @@ mod imp {
 
         #[property(get, set = Self::set_uri)]
         uri: RefCell<String>,
+        // Loaded instead when `uri` fails
+        #[property(get, set)]
+        fallback_uri: RefCell<String>,
+        // The only uri whose image is still wanted, results for any other one are dropped
+        requested: RefCell<String>,
         #[property(get, set)]
         height: Cell<i32>,
         #[property(get, set)]
@@ -76,44 +83,72 @@ mod imp {
             if uri.is_empty() {
                 return;
             }
+            self.uri.replace(uri.clone());
+            self.requested.replace(uri.clone());
 
             MainContext::default().spawn_local(
                 glib::clone!(@strong uri, @weak self as _self => async move {
-                    if let Ok(mut response) = isahc::get_async(uri).await {
-                        if response.status().is_success() {
-                            if let Ok(image_data) = response.bytes().await {
-                                let pixbuf_loader = PixbufLoader::new();
-                                if pixbuf_loader.write(image_data.as_slice()).is_ok() {
-                                    pixbuf_loader.close().unwrap();
-                                    let width = _self.obj().width();
-                                    let height = _self.obj().height();
-                                    let pixbuf = pixbuf_loader.pixbuf().unwrap();
-                                    let pixbuf = if width > 0 && height > 0 {
-                                        if let Some(scaled_pixbuf) = pixbuf.scale_simple(width, height, InterpType::Nearest) {
-                                            scaled_pixbuf
-                                        } else {
-                                            pixbuf
-                                        }
-                                    } else {
-                                        pixbuf
-                                    };
-
-                                    // Set the Pixbuf on the Image widget
-                                    _self.picture.set_pixbuf(Some(&pixbuf));
-                                    _self.picture.set_width_request(width);
-                                    _self.picture.set_height_request(height);
-                                    _self.stack.set_visible_child_name("picture");
-                                    _self.spinner.set_spinning(false);
-                                    _self.spinner.stop();
-                                    return;
-                                }
-                            }
-                        }
+                    if _self.load(&uri).await {
+                        return;
+                    }
+                    // Another uri was set while this one was loading
+                    if *_self.requested.borrow() != uri {
+                        return;
+                    }
+                    let fallback_uri = _self.fallback_uri.borrow().clone();
+                    if fallback_uri.is_empty() || fallback_uri == uri {
+                        _self.stack.set_visible_child_name("error");
+                        return;
+                    }
+                    _self.requested.replace(fallback_uri.clone());
+                    let loaded = _self.load(&fallback_uri).await;
+                    if !loaded && *_self.requested.borrow() == fallback_uri {
+                        _self.stack.set_visible_child_name("error");
                     }
-                    _self.stack.set_visible_child_name("error");
                 }),
             );
         }
+
+        async fn load(&self, uri: &str) -> bool {
+            if let Ok(mut response) = api::get(uri).await {
+                if response.status().is_success() {
+                    if let Ok(image_data) = response.bytes().await {
+                        // The uri may have changed while this one was loading
+                        if *self.requested.borrow() != uri {
+                            return true;
+                        }
+                        let pixbuf_loader = PixbufLoader::new();
+                        if pixbuf_loader.write(image_data.as_slice()).is_ok() {
+                            pixbuf_loader.close().unwrap();
+                            let width = self.obj().width();
+                            let height = self.obj().height();
+                            let pixbuf = pixbuf_loader.pixbuf().unwrap();
+                            let pixbuf = if width > 0 && height > 0 {
+                                if let Some(scaled_pixbuf) =
+                                    pixbuf.scale_simple(width, height, InterpType::Nearest)
+                                {
+                                    scaled_pixbuf
+                                } else {
+                                    pixbuf
+                                }
+                            } else {
+                                pixbuf
+                            };
+
+                            // Set the Pixbuf on the Image widget
+                            self.picture.set_pixbuf(Some(&pixbuf));
+                            self.picture.set_width_request(width);
+                            self.picture.set_height_request(height);
+                            self.stack.set_visible_child_name("picture");
+                            self.spinner.set_spinning(false);
+                            self.spinner.stop();
+                            return true;
+                        }
+                    }
+                }
+            }
+            false
+        }
     }
 }
 
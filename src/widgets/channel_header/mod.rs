@@ -0,0 +1,191 @@
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use glib::{clone, Object};
+use gtk::glib;
+use gtk::CompositeTemplate;
+
+use crate::api::DetailedChannel;
+use crate::markup::{external_links, html_to_pango, parse_link, Link};
+use crate::utils::format_number_magnitude;
+use crate::widgets::async_image::AsyncImage;
+
+// Banners are very wide, this is about as wide as the header gets
+const BANNER_WIDTH: u32 = 1060;
+const AVATAR_SIZE: u32 = 176;
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Default, Debug, CompositeTemplate)]
+    #[template(resource = "/dev/quark97/Pryvid/channel_header.ui")]
+    pub struct ChannelHeader {
+        #[template_child]
+        pub banner_image: TemplateChild<AsyncImage>,
+        #[template_child]
+        pub avatar_image: TemplateChild<AsyncImage>,
+        #[template_child]
+        pub title_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub verified_icon: TemplateChild<gtk::Image>,
+        #[template_child]
+        pub stats_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub description_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub links_box: TemplateChild<gtk::FlowBox>,
+        #[template_child]
+        pub tags_box: TemplateChild<gtk::FlowBox>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ChannelHeader {
+        const NAME: &'static str = "ChannelHeader";
+        type Type = super::ChannelHeader;
+        type ParentType = adw::Bin;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for ChannelHeader {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+            self.description_label.connect_activate_link(
+                clone!(@weak obj => @default-return glib::Propagation::Proceed, move |_, uri| {
+                    let result = match parse_link(uri) {
+                        Link::Hashtag(tag) => {
+                            obj.activate_action("win.open-hashtag", Some(&tag.to_variant()))
+                        }
+                        _ => obj.activate_action("win.open-link", Some(&uri.to_variant())),
+                    };
+                    if let Err(error) = result {
+                        log::warn!("Failed to open {}: {}", uri, error);
+                    }
+                    glib::Propagation::Stop
+                }),
+            );
+        }
+    }
+    impl WidgetImpl for ChannelHeader {}
+    impl BinImpl for ChannelHeader {}
+}
+
+glib::wrapper! {
+    pub struct ChannelHeader(ObjectSubclass<imp::ChannelHeader>)
+        @extends adw::Bin, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl Default for ChannelHeader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChannelHeader {
+    pub fn new() -> Self {
+        Object::builder().build()
+    }
+
+    /// Shows `channel`, leaving out whatever it doesn't have
+    pub fn set_channel(&self, channel: &DetailedChannel) {
+        let imp = self.imp();
+
+        match channel.banner(BANNER_WIDTH) {
+            Some(banner) => {
+                imp.banner_image.set_uri(banner.uri.clone());
+                imp.banner_image.set_visible(true);
+            }
+            None => imp.banner_image.set_visible(false),
+        }
+        match channel.thumbnail(AVATAR_SIZE) {
+            Some(thumbnail) => {
+                imp.avatar_image.set_uri(thumbnail.uri.clone());
+                imp.avatar_image.set_visible(true);
+            }
+            None => imp.avatar_image.set_visible(false),
+        }
+
+        imp.title_label.set_label(&channel.title);
+        imp.verified_icon.set_visible(channel.verified);
+
+        let stats = stats(channel);
+        imp.stats_label.set_label(&stats);
+        imp.stats_label.set_visible(!stats.is_empty());
+
+        let description = html_to_pango(&channel.description_html);
+        imp.description_label.set_markup(description.trim());
+        imp.description_label
+            .set_visible(!description.trim().is_empty());
+
+        imp.links_box.remove_all();
+        let links = external_links(&channel.description_html);
+        for link in &links {
+            imp.links_box.append(&link_button(link));
+        }
+        imp.links_box.set_visible(!links.is_empty());
+
+        imp.tags_box.remove_all();
+        for tag in &channel.tags {
+            imp.tags_box.append(&tag_button(tag));
+        }
+        imp.tags_box.set_visible(!channel.tags.is_empty());
+    }
+}
+
+/// Subscribers, views and when the channel was created, as far as they are known
+fn stats(channel: &DetailedChannel) -> String {
+    let mut stats = Vec::new();
+    if channel.subscribers > 0 {
+        stats.push(format!(
+            "{} subscribers",
+            format_number_magnitude(channel.subscribers)
+        ));
+    }
+    if channel.total_views > 0 {
+        let views = u64::try_from(channel.total_views).unwrap_or(u64::MAX);
+        stats.push(format!("{} views", format_number_magnitude(views)));
+    }
+    if let Some(joined) = Some(channel.joined)
+        .filter(|x| *x > 0)
+        .and_then(|x| glib::DateTime::from_unix_local(x).ok())
+        .and_then(|x| x.format("%B %Y").ok())
+    {
+        stats.push(format!("Joined {}", joined));
+    }
+    stats.join(" · ")
+}
+
+fn link_button(uri: &str) -> gtk::Button {
+    // Only the site is shown, the whole link is in the tooltip
+    let label = glib::Uri::parse(uri, glib::UriFlags::NONE)
+        .ok()
+        .and_then(|x| x.host())
+        .map(|x| x.trim_start_matches("www.").to_string())
+        .unwrap_or_else(|| uri.to_string());
+    let button = gtk::Button::builder()
+        .label(label)
+        .tooltip_text(uri)
+        .action_name("win.open-link")
+        .action_target(&uri.to_variant())
+        .build();
+    button.add_css_class("flat");
+    button
+}
+
+fn tag_button(tag: &str) -> gtk::Button {
+    let button = gtk::Button::builder()
+        .label(tag)
+        .action_name("win.search")
+        .action_target(&tag.to_variant())
+        .build();
+    button.add_css_class("flat");
+    button.add_css_class("caption");
+    button
+}
@@ -1,11 +1,12 @@
 use adw::prelude::*;
 use adw::subclass::prelude::*;
-use glib::{Object, Properties};
+use glib::{clone, Object, Properties};
 use gtk::glib;
 use gtk::CompositeTemplate;
 use std::cell::{Cell, RefCell};
 
 use crate::api::DetailedChannel;
+use crate::markup::html_to_pango;
 use crate::utils::format_number_magnitude;
 use crate::widgets::async_image::AsyncImage;
 
@@ -51,6 +52,26 @@ mod imp {
     }
 
     impl ObjectImpl for ChannelInfoWindow {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+            // Links are handled by the main window, which we are always transient for
+            self.description_label.connect_activate_link(
+                clone!(@weak obj => @default-return glib::Propagation::Proceed, move |_, uri| {
+                    match obj.transient_for() {
+                        Some(parent) => {
+                            parent
+                                .activate_action("win.open-link", Some(&uri.to_variant()))
+                                .unwrap();
+                            obj.close();
+                            glib::Propagation::Stop
+                        }
+                        None => glib::Propagation::Proceed,
+                    }
+                }),
+            );
+        }
+
         fn properties() -> &'static [glib::ParamSpec] {
             Self::derived_properties()
         }
@@ -85,28 +106,21 @@ glib::wrapper! {
 
 impl ChannelInfoWindow {
     pub fn new(channel: &DetailedChannel) -> Self {
-        Object::builder()
-            .property(
-                "banner",
-                &channel
-                    .banners
-                    .iter()
-                    .find(|x| x.width == 512)
-                    .unwrap_or(channel.banners.last().unwrap())
-                    .uri,
-            )
+        let banner = channel.banner(512).map(|x| x.uri.clone());
+        let obj: Self = Object::builder()
+            .property("banner", banner.clone().unwrap_or_default())
             .property("title", &channel.title)
             .property(
                 "thumbnail",
-                &channel
-                    .thumbnails
-                    .iter()
-                    .find(|x| x.width == 512)
-                    .unwrap_or(channel.banners.last().unwrap())
-                    .uri,
+                channel
+                    .thumbnail(512)
+                    .map(|x| x.uri.clone())
+                    .unwrap_or_default(),
             )
-            .property("description", &channel.description)
+            .property("description", html_to_pango(&channel.description_html))
             .property("subscribers", channel.subscribers)
-            .build()
+            .build();
+        obj.imp().banner_image.set_visible(banner.is_some());
+        obj
     }
 }
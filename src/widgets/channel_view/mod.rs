@@ -9,8 +9,9 @@ use std::sync::Arc;
 use crate::api::{DetailedChannel, Instance};
 use crate::appmodel::AppModel;
 use crate::widgets::{
-    channel_info_window::ChannelInfoWindow, content_grid::ContentGrid,
-    instance_indicator::InstanceIndicator, result_page::ResultPage, result_page::ResultPageState,
+    channel_header::ChannelHeader, channel_info_window::ChannelInfoWindow,
+    content_grid::ContentGrid, instance_indicator::InstanceIndicator, result_page::ResultPage,
+    result_page::ResultPageState,
 };
 
 mod imp {
@@ -24,6 +25,12 @@ mod imp {
         pub model: OnceCell<Arc<AppModel>>,
         pub channel: RefCell<Option<DetailedChannel>>,
 
+        #[template_child]
+        pub header_revealer: TemplateChild<gtk::Revealer>,
+        #[template_child]
+        pub channel_header: TemplateChild<ChannelHeader>,
+        #[template_child]
+        pub content_stack: TemplateChild<gtk::Stack>,
         #[template_child]
         pub view_stack: TemplateChild<adw::ViewStack>,
         #[template_child]
@@ -33,10 +40,18 @@ mod imp {
         #[template_child]
         pub channels_grid: TemplateChild<ContentGrid>,
         #[template_child]
+        pub search_grid: TemplateChild<ContentGrid>,
+        #[template_child]
+        pub search_bar: TemplateChild<gtk::SearchBar>,
+        #[template_child]
+        pub search_entry: TemplateChild<gtk::SearchEntry>,
+        #[template_child]
         pub instance_indicator: TemplateChild<InstanceIndicator>,
         #[template_child]
         pub info_button: TemplateChild<gtk::Button>,
         #[template_child]
+        pub subscribe_button: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
         pub result_page: TemplateChild<ResultPage>,
 
         #[property(get, set)]
@@ -60,6 +75,12 @@ mod imp {
     }
 
     impl ObjectImpl for ChannelView {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.search_bar.connect_entry(&*self.search_entry);
+            self.obj().setup_header_collapse();
+        }
+
         fn properties() -> &'static [glib::ParamSpec] {
             Self::derived_properties()
         }
@@ -93,9 +114,43 @@ mod imp {
             }
         }
         #[template_callback]
+        fn on_subscribe_toggled(&self, button: gtk::ToggleButton) {
+            let obj = self.obj();
+            let model = obj.model();
+            let channel_id = obj.channel_id();
+            // Also called when the button is synced with the profile
+            if model.is_subscribed(&channel_id) != button.is_active() {
+                let name = self
+                    .channel
+                    .borrow()
+                    .as_ref()
+                    .map(|x| x.title.clone())
+                    .unwrap_or_default();
+                model.set_subscribed(&channel_id, &name, button.is_active());
+            }
+            obj.update_subscribe_button();
+        }
+        #[template_callback]
         fn on_refresh_clicked(&self, _: ResultPage) {
             self.obj().fetch_content();
         }
+        #[template_callback]
+        fn on_search_mode_changed(&self) {
+            if self.search_bar.is_search_mode() {
+                self.search_entry.grab_focus();
+            } else {
+                self.search_grid.stop_paginating();
+                self.content_stack.set_visible_child_name("channel");
+            }
+        }
+        #[template_callback]
+        fn on_search_entry_activated(&self, search_entry: gtk::SearchEntry) {
+            self.obj().search(search_entry.text().trim().to_string());
+        }
+        #[template_callback]
+        fn on_search_refresh(&self, grid: ContentGrid) {
+            grid.reload_pages();
+        }
     }
 }
 
@@ -108,7 +163,17 @@ glib::wrapper! {
 impl ChannelView {
     pub fn new(model: Arc<AppModel>, channel_id: String) -> Self {
         let obj: Self = Object::builder().property("channel-id", channel_id).build();
-        obj.imp().model.set(model).unwrap();
+        let imp = obj.imp();
+        for grid in [
+            &*imp.videos_grid,
+            &*imp.playlists_grid,
+            &*imp.channels_grid,
+            &*imp.search_grid,
+        ] {
+            grid.set_model(model.clone());
+        }
+        imp.model.set(model).unwrap();
+        obj.update_subscribe_button();
         obj.fetch_content();
         obj
     }
@@ -117,6 +182,52 @@ impl ChannelView {
         self.imp().model.get().unwrap().clone()
     }
 
+    fn setup_header_collapse(&self) {
+        let imp = self.imp();
+        for grid in [
+            &*imp.videos_grid,
+            &*imp.playlists_grid,
+            &*imp.channels_grid,
+            &*imp.search_grid,
+        ] {
+            grid.vadjustment()
+                .connect_value_changed(clone!(@weak self as obj => move |_| {
+                    obj.update_header();
+                }));
+        }
+        imp.view_stack
+            .connect_visible_child_notify(clone!(@weak self as obj => move |_| {
+                obj.update_header();
+            }));
+        imp.content_stack
+            .connect_visible_child_notify(clone!(@weak self as obj => move |_| {
+                obj.update_header();
+            }));
+    }
+
+    /// Shows the header only while the visible grid is scrolled to the top
+    fn update_header(&self) {
+        let imp = self.imp();
+        let grid = if imp.content_stack.visible_child_name().as_deref() == Some("search") {
+            Some((*imp.search_grid).clone())
+        } else {
+            imp.view_stack.visible_child().and_downcast::<ContentGrid>()
+        };
+        let at_top = grid.map_or(true, |x| x.vadjustment().value() <= 0.0);
+        imp.header_revealer.set_reveal_child(at_top);
+    }
+
+    fn update_subscribe_button(&self) {
+        let button = &self.imp().subscribe_button;
+        let subscribed = self.model().is_subscribed(&self.channel_id());
+        button.set_active(subscribed);
+        button.set_label(if subscribed {
+            "Subscribed"
+        } else {
+            "Subscribe"
+        });
+    }
+
     async fn fetch_channel(&self, instance: &Arc<Instance>, channel_id: &str) -> ResultPageState {
         let imp = self.imp();
         let videos_grid = &imp.videos_grid;
@@ -128,6 +239,7 @@ impl ChannelView {
         match instance.channel(channel_id).await {
             Ok(channel) => {
                 self.set_title(&channel.title);
+                imp.channel_header.set_channel(&channel);
                 imp.channel.replace(Some(channel.clone()));
                 videos_grid.set_state(if channel.videos.is_empty() {
                     ResultPageState::Message((
@@ -176,6 +288,32 @@ impl ChannelView {
         }
     }
 
+    /// Shows the uploads of this channel that match `query` instead of the channel's pages
+    fn search(&self, query: String) {
+        let imp = self.imp();
+        if query.is_empty() {
+            imp.search_grid.stop_paginating();
+            imp.content_stack.set_visible_child_name("channel");
+            return;
+        }
+        imp.content_stack.set_visible_child_name("search");
+
+        let model = self.model();
+        let channel_id = self.channel_id();
+        imp.search_grid.paginate(
+            (
+                "system-search-symbolic".into(),
+                "No Results".into(),
+                "Nothing on this channel matches your search".into(),
+            ),
+            move |page| {
+                let instance = model.invidious().get_instance();
+                let (channel_id, query) = (channel_id.clone(), query.clone());
+                async move { instance.channel_search(&channel_id, &query, page).await }
+            },
+        );
+    }
+
     fn fetch_content(&self) {
         self.imp().result_page.set_state(ResultPageState::Loading);
         MainContext::default().spawn_local(clone!(@weak self as obj => async move {
@@ -1,15 +1,23 @@
 use adw::prelude::*;
 use adw::subclass::prelude::*;
-use glib::{subclass::Signal, Object, Properties};
+use glib::{clone, subclass::Signal, MainContext, Object, Properties};
 use gtk::glib;
 use gtk::CompositeTemplate;
 use once_cell::sync::Lazy;
-use std::cell::Cell;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::api::Channel;
 use crate::api::Content;
+use crate::api::Error;
 use crate::api::Playlist;
 use crate::api::Video;
+use crate::appmodel::AppModel;
+use crate::content_filter::ContentFilter;
 use crate::widgets::{
     channel_button::ChannelButton,
     playlist_button::PlaylistButton,
@@ -17,6 +25,23 @@ use crate::widgets::{
     video_button::VideoButton,
 };
 
+type PageFuture = Pin<Box<dyn Future<Output = Result<Vec<Content>, Error>>>>;
+
+/// Loads content a page at a time, see `ContentGrid::paginate`
+#[derive(Clone)]
+pub struct Pager {
+    // Pages start at 1
+    load: Rc<dyn Fn(u32) -> PageFuture>,
+    // Icon, title and description shown when the first page is empty
+    empty: (String, String, String),
+}
+
+impl fmt::Debug for Pager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pager").field("empty", &self.empty).finish()
+    }
+}
+
 mod imp {
 
     use super::*;
@@ -29,11 +54,27 @@ mod imp {
         pub flowbox: TemplateChild<gtk::FlowBox>,
         #[template_child]
         pub result_page: TemplateChild<ResultPage>,
+        #[template_child]
+        pub scrolled_window: TemplateChild<gtk::ScrolledWindow>,
 
         #[property(get, set)]
         pub refreshable: Cell<bool>,
         #[property(get, set)]
         pub loaded: Cell<bool>,
+
+        pub pager: RefCell<Option<Pager>>,
+        // Last page that was loaded, 0 before the first one
+        pub page: Cell<u32>,
+        pub loading: Cell<bool>,
+        // Set once a page comes back empty
+        pub exhausted: Cell<bool>,
+        // Bumped whenever the pages start over, so pages of what was shown before are dropped
+        pub generation: Cell<u64>,
+
+        pub model: OnceCell<Arc<AppModel>>,
+        pub filter: RefCell<ContentFilter>,
+        // Settings outlive the grid, so the handler is disconnected when it goes away
+        pub filter_handler: RefCell<Option<glib::SignalHandlerId>>,
     }
 
     #[glib::object_subclass]
@@ -53,6 +94,19 @@ mod imp {
     }
 
     impl ObjectImpl for ContentGrid {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+            obj.setup_filter();
+            // Paginated content loads its next page once the end is reached
+            self.scrolled_window
+                .connect_edge_reached(clone!(@weak obj => move |_, position| {
+                    if position == gtk::PositionType::Bottom {
+                        obj.load_next_page();
+                    }
+                }));
+        }
+
         fn signals() -> &'static [glib::subclass::Signal] {
             static SIGNALS: Lazy<Vec<Signal>> =
                 Lazy::new(|| vec![Signal::builder("refresh").build()]);
@@ -70,6 +124,12 @@ mod imp {
         fn property(&self, id: usize, pspec: &glib::ParamSpec) -> glib::Value {
             self.derived_property(id, pspec)
         }
+
+        fn dispose(&self) {
+            if let (Some(model), Some(handler)) = (self.model.get(), self.filter_handler.take()) {
+                model.settings().disconnect(handler);
+            }
+        }
     }
     impl WidgetImpl for ContentGrid {}
     impl BinImpl for ContentGrid {}
@@ -100,6 +160,11 @@ impl ContentGrid {
         Object::builder().build()
     }
 
+    /// Tells how far the grid is scrolled
+    pub fn vadjustment(&self) -> gtk::Adjustment {
+        self.imp().scrolled_window.vadjustment()
+    }
+
     pub fn set_state(&self, state: ResultPageState) {
         self.imp().result_page.set_state(state);
     }
@@ -111,8 +176,158 @@ impl ContentGrid {
         }
     }
 
+    /// Shows content that is loaded a page at a time by `load`, starting with the first
+    /// page. `empty` is the icon, title and description shown if there is nothing at all
+    pub fn paginate<F, Fut>(&self, empty: (String, String, String), load: F)
+    where
+        F: Fn(u32) -> Fut + 'static,
+        Fut: Future<Output = Result<Vec<Content>, Error>> + 'static,
+    {
+        self.imp().pager.replace(Some(Pager {
+            load: Rc::new(move |page| -> PageFuture { Box::pin(load(page)) }),
+            empty,
+        }));
+        self.reload_pages();
+    }
+
+    /// Starts the paginated content over from the first page
+    pub fn reload_pages(&self) {
+        let imp = self.imp();
+        let Some(pager) = imp.pager.borrow().clone() else {
+            return;
+        };
+        let generation = self.restart_pages();
+        imp.loading.set(true);
+        self.clear();
+        self.set_state(ResultPageState::Loading);
+
+        MainContext::default().spawn_local(clone!(@weak self as obj => async move {
+            let result = (pager.load)(1).await;
+            let imp = obj.imp();
+            if imp.generation.get() != generation {
+                return;
+            }
+            obj.set_state(match result {
+                Ok(content) if content.is_empty() => {
+                    imp.exhausted.set(true);
+                    ResultPageState::Message(pager.empty.clone())
+                }
+                Ok(content) => {
+                    imp.page.set(1);
+                    obj.set_content(&content);
+                    ResultPageState::Success
+                }
+                Err(error) => ResultPageState::Error(error.to_string()),
+            });
+            imp.loading.set(false);
+        }));
+    }
+
+    /// Stops loading pages, anything still on its way is dropped
+    pub fn stop_paginating(&self) {
+        self.imp().pager.take();
+        self.restart_pages();
+    }
+
+    fn restart_pages(&self) -> u64 {
+        let imp = self.imp();
+        let generation = imp.generation.get() + 1;
+        imp.generation.set(generation);
+        imp.page.set(0);
+        imp.loading.set(false);
+        imp.exhausted.set(false);
+        generation
+    }
+
+    fn load_next_page(&self) {
+        let imp = self.imp();
+        let Some(pager) = imp.pager.borrow().clone() else {
+            return;
+        };
+        if imp.loading.get() || imp.exhausted.get() || imp.page.get() == 0 {
+            return;
+        }
+        imp.loading.set(true);
+        let generation = imp.generation.get();
+        let page = imp.page.get() + 1;
+
+        MainContext::default().spawn_local(clone!(@weak self as obj => async move {
+            let result = (pager.load)(page).await;
+            let imp = obj.imp();
+            if imp.generation.get() != generation {
+                return;
+            }
+            match result {
+                Ok(content) if content.is_empty() => imp.exhausted.set(true),
+                Ok(content) => {
+                    imp.page.set(page);
+                    obj.append_content(&content);
+                }
+                // Reaching the end again tries once more
+                Err(error) => log::warn!("Failed to load page {}: {}", page, error),
+            }
+            imp.loading.set(false);
+        }));
+    }
+
+    /// Has to be called before anything is shown, the model provides the content filter
+    pub fn set_model(&self, model: Arc<AppModel>) {
+        let imp = self.imp();
+        imp.filter.replace(model.content_filter());
+        // Hidden items stay in the grid, so they come back as soon as the filter changes
+        let handler = model.settings().connect_changed(
+            Some("content-filter"),
+            clone!(@weak self as obj => move |_, _| {
+                let imp = obj.imp();
+                imp.filter.replace(obj.model().content_filter());
+                imp.flowbox.invalidate_filter();
+            }),
+        );
+        imp.filter_handler.replace(Some(handler));
+        imp.flowbox.invalidate_filter();
+        imp.model.set(model).unwrap();
+    }
+
+    fn model(&self) -> Arc<AppModel> {
+        self.imp()
+            .model
+            .get()
+            .expect("`model` should be set by calling `set_model`")
+            .clone()
+    }
+
+    fn setup_filter(&self) {
+        let imp = self.imp();
+        imp.flowbox.set_filter_func(
+            clone!(@weak self as obj => @default-return true, move |child| {
+                let filter = obj.imp().filter.borrow();
+                match child.child() {
+                    Some(widget) => {
+                        if let Some(video) = widget.downcast_ref::<VideoButton>() {
+                            filter.allows(
+                                &video.author_id(),
+                                &video.title(),
+                                video.length(),
+                                video.live(),
+                            )
+                        } else if let Some(channel) = widget.downcast_ref::<ChannelButton>() {
+                            !filter.is_blocked(&channel.author_id())
+                        } else if let Some(playlist) = widget.downcast_ref::<PlaylistButton>() {
+                            filter.allows_playlist(&playlist.author_id(), &playlist.title())
+                        } else {
+                            true
+                        }
+                    }
+                    None => true,
+                }
+            }),
+        );
+    }
+
     fn add_video(&self, video: &Video) {
-        self.imp().flowbox.append(&VideoButton::new(video));
+        self.imp()
+            .flowbox
+            .append(&VideoButton::new(video, &self.model()));
     }
 
     fn add_channel(&self, channel: &Channel) {
@@ -125,6 +340,10 @@ impl ContentGrid {
 
     pub fn set_content(&self, content: &[Content]) {
         self.clear();
+        self.append_content(content);
+    }
+    /// Adds content after what is already shown
+    pub fn append_content(&self, content: &[Content]) {
         for item in content {
             match item {
                 Content::Video(video) => self.add_video(video),
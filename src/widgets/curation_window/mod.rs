@@ -1,12 +1,13 @@
 use adw::prelude::*;
 use adw::subclass::prelude::*;
+use futures::stream::{AbortHandle, Abortable, StreamExt};
 use glib::{clone, closure_local, MainContext, Object};
 use gtk::glib;
 use gtk::{CompositeTemplate, Ordering};
 use std::cell::OnceCell;
 use std::sync::Arc;
 
-use crate::api::Instances;
+use crate::api::{probe_instances, Instance, Instances};
 use crate::appmodel::AppModel;
 use crate::widgets::curation_instance_row::CurationInstanceRow;
 
@@ -25,6 +26,10 @@ mod imp {
         pub instances_listbox: TemplateChild<gtk::ListBox>,
         #[template_child]
         pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        pub progress_revealer: TemplateChild<gtk::Revealer>,
+        #[template_child]
+        pub progress_bar: TemplateChild<gtk::ProgressBar>,
     }
 
     #[glib::object_subclass]
@@ -59,11 +64,22 @@ impl CurationWindow {
         let obj: Self = Object::builder().build();
         obj.imp().model.set(model).unwrap();
         obj.imp().instances.set(instances).unwrap();
+        obj.setup_sorting();
         obj.build();
         obj.ping();
         obj
     }
 
+    /// Creates an empty window that fills up as `instances` are probed
+    pub fn discover(model: Arc<AppModel>, instances: Instances) -> Self {
+        let obj: Self = Object::builder().build();
+        obj.imp().model.set(model).unwrap();
+        obj.imp().instances.set(instances).unwrap();
+        obj.setup_sorting();
+        obj.probe();
+        obj
+    }
+
     fn instances(&self) -> Instances {
         self.imp().instances.get().unwrap().clone()
     }
@@ -102,7 +118,58 @@ impl CurationWindow {
                 row.set_ping_state(PingState::Success(ping));
             }
 
-            instances_listbox.set_sort_func(move |row1, row2| {
+            instances_listbox.invalidate_sort();
+        }));
+    }
+
+    fn probe(&self) {
+        let imp = self.imp();
+        let total = self.instances().len();
+        imp.progress_revealer.set_reveal_child(true);
+        self.set_progress(0, total);
+
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        self.connect_destroy(move |_| abort_handle.abort());
+
+        let mut results = Abortable::new(probe_instances(self.instances()), abort_registration);
+        MainContext::default().spawn_local(clone!(@weak self as window => async move {
+            let mut done = 0;
+            while let Some((instance, result)) = results.next().await {
+                done += 1;
+                window.set_progress(done, total);
+                if result.is_ok() {
+                    let ping = instance
+                        .info
+                        .read()
+                        .unwrap()
+                        .health
+                        .latency_percentile(50)
+                        .map(|x| PingState::Success(x.into()))
+                        .unwrap_or_default();
+                    window.add_instance(instance, ping);
+                }
+            }
+            window.imp().progress_revealer.set_reveal_child(false);
+            if window.imp().instances_listbox.row_at_index(0).is_none() {
+                window.toast_message("No reachable instances were found");
+            }
+        }));
+    }
+
+    fn set_progress(&self, done: usize, total: usize) {
+        let progress_bar = &self.imp().progress_bar;
+        progress_bar.set_fraction(if total == 0 {
+            1.0
+        } else {
+            done as f64 / total as f64
+        });
+        progress_bar.set_text(Some(&format!("Checked {done} of {total} instances")));
+    }
+
+    fn setup_sorting(&self) {
+        self.imp()
+            .instances_listbox
+            .set_sort_func(move |row1, row2| {
                 let row1 = row1.clone().downcast::<CurationInstanceRow>().unwrap();
                 let row2 = row2.clone().downcast::<CurationInstanceRow>().unwrap();
                 if let PingState::Success(ping1) = row1.ping_state() {
@@ -121,22 +188,28 @@ impl CurationWindow {
                     Ordering::Equal
                 }
             });
-        }));
     }
 
     fn build(&self) {
-        let instances = self.instances();
-        let instances_listbox = &self.imp().instances_listbox;
-        for instance in instances {
+        for instance in self.instances() {
             {
                 let info = instance.info.read().unwrap();
                 if info.has_popular.is_none() || info.has_trending.is_none() {
                     continue;
                 }
             }
-            let is_instance_added = self.model().invidious().is_added(&instance);
-            let row = CurationInstanceRow::new(instance.clone(), is_instance_added);
-            row.connect_closure("toggle", false, closure_local!(@watch self as window => move |row: CurationInstanceRow| {
+            self.add_instance(instance, PingState::NotPinged);
+        }
+    }
+
+    fn add_instance(&self, instance: Arc<Instance>, ping: PingState) {
+        let is_instance_added = self.model().invidious().is_added(&instance);
+        let row = CurationInstanceRow::new(instance.clone(), is_instance_added);
+        row.set_ping_state(ping);
+        row.connect_closure(
+            "toggle",
+            false,
+            closure_local!(@watch self as window => move |row: CurationInstanceRow| {
                 let instance = row.instance();
                 if row.added() {
                     if let Err(error) = window.model().invidious().remove_instance(&instance.uri) {
@@ -148,8 +221,8 @@ impl CurationWindow {
                     window.model().invidious().push_instance(instance).unwrap();
                     row.set_added(true);
                 }
-            }));
-            instances_listbox.append(&row);
-        }
+            }),
+        );
+        self.imp().instances_listbox.append(&row);
     }
 }
@@ -0,0 +1,118 @@
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use glib::{Object, Properties};
+use gtk::glib;
+use gtk::CompositeTemplate;
+use std::cell::{OnceCell, RefCell};
+use std::sync::Arc;
+
+use crate::api::Content;
+use crate::appmodel::AppModel;
+use crate::widgets::{content_grid::ContentGrid, instance_indicator::InstanceIndicator};
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Default, Debug, CompositeTemplate, Properties)]
+    #[template(resource = "/dev/quark97/Pryvid/hashtag_view.ui")]
+    #[properties(wrapper_type = super::HashtagView)]
+    pub struct HashtagView {
+        pub model: OnceCell<Arc<AppModel>>,
+
+        // Without the leading #
+        #[property(get, set)]
+        pub hashtag: RefCell<String>,
+
+        #[template_child]
+        pub instance_indicator: TemplateChild<InstanceIndicator>,
+        #[template_child]
+        pub videos_grid: TemplateChild<ContentGrid>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for HashtagView {
+        const NAME: &'static str = "HashtagView";
+        type Type = super::HashtagView;
+        type ParentType = adw::NavigationPage;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for HashtagView {
+        fn properties() -> &'static [glib::ParamSpec] {
+            Self::derived_properties()
+        }
+
+        fn set_property(&self, id: usize, value: &glib::Value, pspec: &glib::ParamSpec) {
+            self.derived_set_property(id, value, pspec)
+        }
+
+        fn property(&self, id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+            self.derived_property(id, pspec)
+        }
+    }
+    impl WidgetImpl for HashtagView {}
+    impl NavigationPageImpl for HashtagView {}
+
+    #[gtk::template_callbacks]
+    impl HashtagView {
+        #[template_callback]
+        fn on_refresh(&self, _: ContentGrid) {
+            self.obj().fetch_content();
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct HashtagView(ObjectSubclass<imp::HashtagView>)
+        @extends adw::NavigationPage, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl HashtagView {
+    pub fn new(model: Arc<AppModel>, hashtag: String) -> Self {
+        let obj: Self = Object::builder().property("hashtag", hashtag).build();
+        obj.set_title(&format!("#{}", obj.hashtag()));
+        obj.imp().videos_grid.set_model(model.clone());
+        obj.imp().model.set(model).unwrap();
+        obj.fetch_content();
+        obj
+    }
+
+    fn model(&self) -> Arc<AppModel> {
+        self.imp().model.get().unwrap().clone()
+    }
+
+    fn fetch_content(&self) {
+        let imp = self.imp();
+        let model = self.model();
+        let hashtag = self.hashtag();
+        imp.instance_indicator
+            .set_uri(model.invidious().get_instance().uri.clone());
+
+        imp.videos_grid.paginate(
+            (
+                "dotted-box-symbolic".into(),
+                "No Videos".into(),
+                "No videos with this hashtag were found".into(),
+            ),
+            move |page| {
+                let instance = model.invidious().get_instance();
+                let hashtag = hashtag.clone();
+                async move {
+                    instance
+                        .hashtag(&hashtag, page)
+                        .await
+                        .map(|videos| videos.into_iter().map(Content::Video).collect::<Vec<_>>())
+                }
+            },
+        );
+    }
+}
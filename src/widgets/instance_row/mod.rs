@@ -7,7 +7,7 @@ use once_cell::sync::Lazy;
 use std::cell::{Cell, OnceCell};
 use std::sync::Arc;
 
-use crate::api::Instance;
+use crate::api::{Endpoint, Instance, InstanceHealth};
 
 mod imp {
 
@@ -100,6 +100,46 @@ impl InstanceRow {
         self.add_row(&row);
     }
 
+    fn add_health_rows(&self, health: &InstanceHealth) {
+        self.add_info_row(
+            "API Access",
+            if health.api_blocked {
+                "Blocked"
+            } else {
+                "Allowed"
+            },
+        );
+        self.add_info_row(
+            "Latency",
+            &match (
+                health.latency_percentile(50),
+                health.latency_percentile(90),
+                health.latency_percentile(99),
+            ) {
+                (Some(p50), Some(p90), Some(p99)) => {
+                    format!("{p50} ms median • {p90} ms p90 • {p99} ms p99")
+                }
+                _ => "Unknown".into(),
+            },
+        );
+        let success_rates = [
+            ("Search", Endpoint::Search),
+            ("Videos", Endpoint::Videos),
+            ("Trending", Endpoint::Trending),
+        ]
+        .into_iter()
+        .map(|(name, endpoint)| {
+            if let Some(rate) = health.endpoint(endpoint).success_rate() {
+                format!("{name} {:.0}%", rate * 100.0)
+            } else {
+                format!("{name} unknown")
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" • ");
+        self.add_info_row("Success Rate", &success_rates);
+    }
+
     fn create_switch(&self) {
         let switch = gtk::Switch::builder()
             .vexpand(false)
@@ -151,6 +191,7 @@ impl InstanceRow {
                     "Closed"
                 },
             );
+            self.add_health_rows(&info.health);
         }
         // Create delete button
         let delete_button = gtk::Button::builder()
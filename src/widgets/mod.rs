@@ -1,19 +1,23 @@
 pub mod async_image;
 pub mod channel_button;
+pub mod channel_header;
 pub mod channel_info_window;
 pub mod channel_view;
 pub mod content_grid;
 pub mod curation_instance_row;
 pub mod curation_window;
+pub mod hashtag_view;
 pub mod instance_indicator;
 pub mod instance_row;
 pub mod loading_window;
 pub mod new_instance_window;
+pub mod now_playing_bar;
 pub mod onboarding;
 pub mod playlist_button;
 pub mod playlist_view;
 pub mod preferences;
 pub mod result_page;
+pub mod seek_bar;
 pub mod video_button;
 pub mod video_view;
 pub mod window;
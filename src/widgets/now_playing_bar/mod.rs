@@ -0,0 +1,264 @@
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use glib::{clone, closure_local, subclass::Signal, Object, Properties};
+use gtk::glib;
+use gtk::CompositeTemplate;
+use once_cell::sync::Lazy;
+use std::cell::{Cell, RefCell};
+
+use crate::api::DetailedVideo;
+use crate::widgets::async_image::AsyncImage;
+use crate::widgets::seek_bar::SeekBar;
+use crate::widgets::video_view::VideoView;
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Default, Debug, CompositeTemplate, Properties)]
+    #[properties(wrapper_type = super::NowPlayingBar)]
+    #[template(resource = "/dev/quark97/Pryvid/now_playing_bar.ui")]
+    pub struct NowPlayingBar {
+        #[template_child]
+        pub seek_bar: TemplateChild<SeekBar>,
+        #[template_child]
+        pub thumbnail_image: TemplateChild<AsyncImage>,
+        #[template_child]
+        pub media_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub video_picture: TemplateChild<gtk::Picture>,
+        #[template_child]
+        pub play_button: TemplateChild<gtk::Button>,
+
+        #[property(get, set)]
+        title: RefCell<String>,
+        #[property(get, set)]
+        author: RefCell<String>,
+        #[property(get, set)]
+        thumbnail: RefCell<String>,
+        #[property(get, set)]
+        video_id: RefCell<String>,
+        #[property(get, set)]
+        active: Cell<bool>,
+        #[property(get, set = Self::set_playing)]
+        playing: Cell<bool>,
+        #[property(get, set)]
+        duration: Cell<i64>,
+        #[property(get, set)]
+        timestamp: Cell<i64>,
+
+        pub stream: RefCell<Option<gtk::MediaStream>>,
+        // Bindings from the stream, dropped when another stream takes over
+        pub bindings: RefCell<Vec<glib::Binding>>,
+        // The video page whose stream is shown while minimized, kept so it can be expanded
+        // again without reloading
+        pub page: RefCell<Option<VideoView>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for NowPlayingBar {
+        const NAME: &'static str = "NowPlayingBar";
+        type Type = super::NowPlayingBar;
+        type ParentType = adw::Bin;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for NowPlayingBar {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+            self.seek_bar.connect_closure(
+                "seek",
+                false,
+                closure_local!(@watch obj => move |_: SeekBar, timestamp: i64| {
+                    if let Some(stream) = obj.imp().stream.borrow().as_ref() {
+                        stream.seek(timestamp);
+                    }
+                }),
+            );
+        }
+
+        fn signals() -> &'static [glib::subclass::Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![
+                    Signal::builder("open").build(),
+                    // Emitted with the video's ID when a stream played by the bar itself
+                    // ends. Minimized pages report that on their own
+                    Signal::builder("ended")
+                        .param_types([String::static_type()])
+                        .build(),
+                ]
+            });
+            SIGNALS.as_ref()
+        }
+
+        fn properties() -> &'static [glib::ParamSpec] {
+            Self::derived_properties()
+        }
+
+        fn set_property(&self, id: usize, value: &glib::Value, pspec: &glib::ParamSpec) {
+            self.derived_set_property(id, value, pspec)
+        }
+
+        fn property(&self, id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+            self.derived_property(id, pspec)
+        }
+    }
+    impl WidgetImpl for NowPlayingBar {}
+    impl BinImpl for NowPlayingBar {}
+
+    #[gtk::template_callbacks]
+    impl NowPlayingBar {
+        fn set_playing(&self, playing: bool) {
+            self.playing.set(playing);
+            let (icon, tooltip) = if playing {
+                ("media-playback-pause-symbolic", "Pause")
+            } else {
+                ("media-playback-start-symbolic", "Play")
+            };
+            self.play_button.set_icon_name(icon);
+            self.play_button.set_tooltip_text(Some(tooltip));
+        }
+
+        #[template_callback]
+        fn on_open_clicked(&self, _: gtk::Button) {
+            self.obj().emit_by_name::<()>("open", &[]);
+        }
+
+        #[template_callback]
+        fn on_play_clicked(&self, _: gtk::Button) {
+            if let Some(stream) = self.stream.borrow().as_ref() {
+                stream.set_playing(!stream.is_playing());
+            }
+        }
+
+        #[template_callback]
+        fn on_stop_clicked(&self, _: gtk::Button) {
+            self.obj().stop();
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct NowPlayingBar(ObjectSubclass<imp::NowPlayingBar>)
+        @extends adw::Bin, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl Default for NowPlayingBar {
+    fn default() -> Self {
+        Object::builder().build()
+    }
+}
+
+impl NowPlayingBar {
+    /// Takes over a stream and starts playing it from `start` (microseconds) once it's ready
+    pub fn play(&self, stream: &gtk::MediaStream, video: &DetailedVideo, start: i64) {
+        self.stop();
+        self.attach(stream, video);
+        let video_id = video.id.clone();
+        stream.connect_ended_notify(clone!(@weak self as obj => move |stream| {
+            if stream.is_ended() {
+                obj.emit_by_name::<()>("ended", &[&video_id]);
+            }
+        }));
+        if stream.is_prepared() {
+            stream.seek(start);
+            stream.play();
+        } else {
+            stream.connect_prepared_notify(move |stream| {
+                if stream.is_prepared() {
+                    stream.seek(start);
+                    stream.play();
+                }
+            });
+        }
+    }
+
+    /// Shows the stream of a video page that was navigated away from while it was playing
+    pub fn minimize(&self, video_view: &VideoView) {
+        let (Some(stream), Some(video)) = (video_view.media_stream(), video_view.video()) else {
+            return;
+        };
+        self.stop();
+        let imp = self.imp();
+        self.attach(&stream, &video);
+        imp.video_picture.set_paintable(Some(&stream));
+        imp.media_stack.set_visible_child_name("video");
+        imp.page.replace(Some(video_view.clone()));
+    }
+
+    pub fn page(&self) -> Option<VideoView> {
+        self.imp().page.borrow().clone()
+    }
+
+    /// Hands the minimized video page back without interrupting playback
+    pub fn release(&self) -> Option<VideoView> {
+        let imp = self.imp();
+        let page = imp.page.take()?;
+        self.detach();
+        Some(page)
+    }
+
+    pub fn stop(&self) {
+        let imp = self.imp();
+        if let Some(stream) = imp.stream.borrow().as_ref() {
+            stream.pause();
+        }
+        if let Some(page) = imp.page.take() {
+            page.destroy_media_stream();
+        }
+        self.detach();
+    }
+
+    fn attach(&self, stream: &gtk::MediaStream, video: &DetailedVideo) {
+        let imp = self.imp();
+
+        let thumbnail = video
+            .thumbnails
+            .iter()
+            .find(|x| x.quality.as_deref() == Some("medium"))
+            .or(video.thumbnails.first())
+            .map(|x| x.uri.clone());
+        self.set_title(video.title.as_str());
+        self.set_author(video.author.as_str());
+        self.set_thumbnail(thumbnail.unwrap_or_default());
+        self.set_video_id(video.id.as_str());
+
+        imp.bindings.replace(
+            ["timestamp", "duration", "playing"]
+                .into_iter()
+                .map(|property| {
+                    stream
+                        .bind_property(property, self, property)
+                        .sync_create()
+                        .build()
+                })
+                .collect(),
+        );
+        imp.stream.replace(Some(stream.clone()));
+        self.set_active(true);
+    }
+
+    fn detach(&self) {
+        let imp = self.imp();
+        for binding in imp.bindings.take() {
+            binding.unbind();
+        }
+        imp.stream.take();
+        imp.video_picture
+            .set_paintable(None::<&gtk::gdk::Paintable>);
+        imp.media_stack.set_visible_child_name("thumbnail");
+        self.set_playing(false);
+        self.set_timestamp(0);
+        self.set_duration(0);
+        self.set_active(false);
+    }
+}
@@ -81,6 +81,7 @@ impl PlaylistView {
         let obj: Self = Object::builder()
             .property("playlist-id", playlist_id)
             .build();
+        obj.imp().videos_grid.set_model(model.clone());
         obj.imp().model.set(model).unwrap();
         obj.fetch_content();
         obj
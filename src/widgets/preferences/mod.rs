@@ -3,18 +3,27 @@ use adw::subclass::prelude::*;
 use adw::ResponseAppearance;
 use futures::stream::{AbortHandle, Abortable};
 use glib::{clone, closure_local, MainContext};
-use gtk::glib;
 use gtk::CompositeTemplate;
-use std::cell::OnceCell;
+use gtk::{gio, glib};
+use std::cell::{OnceCell, RefCell};
+use std::fs;
 use std::sync::Arc;
 
-use crate::api::{fetch_instances, Instance, Instances};
+use crate::api::sponsorblock::{SegmentAction, SegmentCategory};
+use crate::api::trending::REGIONS;
+use crate::api::{check_tor, clear_contacted_hosts, contacted_hosts, fetch_instances, Instance};
 use crate::appmodel::AppModel;
+use crate::backup::{Backup, RestoreMode};
+use crate::content_filter::ContentFilter;
+use crate::profiles::Profile;
 use crate::widgets::{
     curation_window::CurationWindow, instance_row::InstanceRow, loading_window::LoadingWindow,
-    new_instance_window::NewInstanceWindow,
+    new_instance_window::NewInstanceWindow, window::PryvidWindow,
 };
 
+// Order matches the proxy mode row in the blueprint
+const PROXY_MODES: [&str; 4] = ["none", "system", "manual", "tor"];
+
 mod imp {
     use super::*;
 
@@ -25,7 +34,62 @@ mod imp {
         pub instances_listbox: TemplateChild<gtk::ListBox>,
         #[template_child]
         pub popover: TemplateChild<gtk::Popover>,
+        #[template_child]
+        pub hidden_services_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub trending_region_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub proxy_mode_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub proxy_uri_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub tor_uri_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub test_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub proxy_media_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub background_playback_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub sponsorblock_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub sponsorblock_server_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub sponsorblock_categories_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub dislikes_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub dislikes_server_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub dearrow_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub dearrow_thumbnails_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub dearrow_server_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub dearrow_thumbnail_server_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub hide_shorts_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub hide_live_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub min_length_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub max_length_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub keywords_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub blocked_channels_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub profile_name_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub profile_pin_row: TemplateChild<adw::PasswordEntryRow>,
+        #[template_child]
+        pub other_profiles_group: TemplateChild<adw::PreferencesGroup>,
         pub model: OnceCell<Arc<AppModel>>,
+        // Keyword and blocked channel rows, rebuilt whenever the filter changes
+        pub filter_rows: RefCell<Vec<(adw::PreferencesGroup, adw::ActionRow)>>,
+        pub profile_rows: RefCell<Vec<adw::ActionRow>>,
     }
 
     #[glib::object_subclass]
@@ -67,6 +131,96 @@ mod imp {
             self.popover.set_visible(false);
             self.obj().show_discover_dialog();
         }
+        #[template_callback]
+        fn on_proxy_uri_apply(&self, row: adw::EntryRow) {
+            let settings = self.obj().model().settings();
+            settings.set_string("proxy-uri", row.text().trim()).unwrap();
+        }
+        #[template_callback]
+        fn on_tor_uri_apply(&self, row: adw::EntryRow) {
+            let settings = self.obj().model().settings();
+            settings
+                .set_string("tor-proxy-uri", row.text().trim())
+                .unwrap();
+        }
+        #[template_callback]
+        fn on_sponsorblock_server_apply(&self, row: adw::EntryRow) {
+            self.obj().apply_server("sponsorblock-server", &row);
+        }
+        #[template_callback]
+        fn on_dislikes_server_apply(&self, row: adw::EntryRow) {
+            self.obj().apply_server("dislikes-server", &row);
+        }
+        #[template_callback]
+        fn on_dearrow_server_apply(&self, row: adw::EntryRow) {
+            self.obj().apply_server("dearrow-server", &row);
+        }
+        #[template_callback]
+        fn on_dearrow_thumbnail_server_apply(&self, row: adw::EntryRow) {
+            self.obj().apply_server("dearrow-thumbnail-server", &row);
+        }
+        #[template_callback]
+        fn on_add_keyword_apply(&self, row: adw::EntryRow) {
+            let keyword = row.text().trim().to_string();
+            if !ContentFilter::is_valid_keyword(&keyword) {
+                self.obj().add_toast(
+                    adw::Toast::builder()
+                        .title("Keyword isn't a valid regular expression")
+                        .build(),
+                );
+                return;
+            }
+            self.obj()
+                .update_filter(|filter| filter.add_keyword(&keyword));
+            row.set_text("");
+        }
+        #[template_callback]
+        fn on_profile_name_apply(&self, row: adw::EntryRow) {
+            let name = row.text().trim().to_string();
+            if name.is_empty() {
+                row.set_text(&self.obj().model().active_profile().name);
+                return;
+            }
+            self.obj()
+                .model()
+                .update_active_profile(|profile| profile.name = name);
+        }
+        #[template_callback]
+        fn on_profile_pin_apply(&self, row: adw::PasswordEntryRow) {
+            let pin = row.text().trim().to_string();
+            let model = self.obj().model();
+            model.update_active_profile(|profile| {
+                profile.set_pin(Some(pin.as_str()).filter(|x| !x.is_empty()))
+            });
+            // Written right away, a lock shouldn't wait for the next autosave
+            model.save_profiles();
+            row.set_text("");
+            self.obj().add_toast(
+                adw::Toast::builder()
+                    .title(if pin.is_empty() {
+                        "PIN removed"
+                    } else {
+                        "PIN set"
+                    })
+                    .build(),
+            );
+        }
+        #[template_callback]
+        fn on_export_backup_clicked(&self, _: gtk::Button) {
+            self.obj().export_backup();
+        }
+        #[template_callback]
+        fn on_restore_backup_clicked(&self, _: gtk::Button) {
+            self.obj().restore_backup();
+        }
+        #[template_callback]
+        fn on_test_button_clicked(&self, _: gtk::Button) {
+            self.obj().test_connection();
+        }
+        #[template_callback]
+        fn on_contacted_hosts_clicked(&self, _: gtk::Button) {
+            self.obj().show_contacted_hosts_dialog();
+        }
     }
 }
 
@@ -80,10 +234,460 @@ impl PryvidPreferencesWindow {
     pub fn new(model: Arc<AppModel>) -> Self {
         let window: Self = glib::Object::builder().build();
         window.imp().model.set(model).unwrap();
+        window.bind_settings();
         window.build();
         window
     }
 
+    fn bind_settings(&self) {
+        let settings = self.model().settings();
+        settings
+            .bind(
+                "discover-hidden-services",
+                &*self.imp().hidden_services_row,
+                "active",
+            )
+            .build();
+
+        settings
+            .bind("proxy-media", &*self.imp().proxy_media_row, "active")
+            .build();
+
+        // Proxy
+        let imp = self.imp();
+        imp.proxy_uri_row.set_text(&settings.string("proxy-uri"));
+        imp.tor_uri_row.set_text(&settings.string("tor-proxy-uri"));
+        imp.proxy_mode_row.set_selected(
+            PROXY_MODES
+                .iter()
+                .position(|x| *x == settings.string("proxy-mode").as_str())
+                .unwrap_or_default() as u32,
+        );
+        imp.proxy_mode_row
+            .connect_selected_notify(clone!(@weak self as window => move |row| {
+                if let Some(mode) = PROXY_MODES.get(row.selected() as usize) {
+                    window.model().settings().set_string("proxy-mode", mode).unwrap();
+                }
+                window.update_proxy_rows();
+            }));
+        self.update_proxy_rows();
+
+        // Filters
+        self.setup_filter_rows();
+        self.setup_profile_rows();
+
+        // Playback
+        settings
+            .bind(
+                "background-playback",
+                &*imp.background_playback_row,
+                "active",
+            )
+            .build();
+
+        // SponsorBlock
+        settings
+            .bind("sponsorblock-enabled", &*imp.sponsorblock_row, "active")
+            .build();
+        imp.sponsorblock_server_row
+            .set_text(&settings.string("sponsorblock-server"));
+        self.build_segment_rows();
+
+        // Return YouTube Dislike
+        settings
+            .bind("dislikes-enabled", &*imp.dislikes_row, "active")
+            .build();
+        imp.dislikes_server_row
+            .set_text(&settings.string("dislikes-server"));
+
+        // DeArrow
+        settings
+            .bind("dearrow-enabled", &*imp.dearrow_row, "active")
+            .build();
+        settings
+            .bind("dearrow-thumbnails", &*imp.dearrow_thumbnails_row, "active")
+            .build();
+        imp.dearrow_server_row
+            .set_text(&settings.string("dearrow-server"));
+        imp.dearrow_thumbnail_server_row
+            .set_text(&settings.string("dearrow-thumbnail-server"));
+
+        self.setup_region_row();
+    }
+
+    fn setup_region_row(&self) {
+        let row = &self.imp().trending_region_row;
+        // The first entry leaves the choice to the instance
+        let names: Vec<&str> = std::iter::once("Chosen by Instance")
+            .chain(REGIONS.iter().map(|(_, name)| *name))
+            .collect();
+        row.set_model(Some(&gtk::StringList::new(&names)));
+        // Needed for searching
+        row.set_expression(Some(gtk::PropertyExpression::new(
+            gtk::StringObject::static_type(),
+            None::<&gtk::Expression>,
+            "string",
+        )));
+
+        let region = self.model().settings().string("trending-region");
+        row.set_selected(
+            REGIONS
+                .iter()
+                .position(|(code, _)| *code == region.as_str())
+                .map_or(0, |index| index as u32 + 1),
+        );
+        row.connect_selected_notify(clone!(@weak self as window => move |row| {
+            let code = match row.selected() {
+                0 => "",
+                index => REGIONS
+                    .get(index as usize - 1)
+                    .map_or("", |(code, _)| code),
+            };
+            window
+                .model()
+                .settings()
+                .set_string("trending-region", code)
+                .unwrap();
+        }));
+    }
+
+    fn setup_filter_rows(&self) {
+        let imp = self.imp();
+        let filter = self.model().content_filter();
+        imp.hide_shorts_row.set_active(filter.hide_shorts);
+        imp.hide_live_row.set_active(filter.hide_live);
+        imp.min_length_row
+            .set_value((filter.min_length / 60) as f64);
+        imp.max_length_row
+            .set_value((filter.max_length / 60) as f64);
+
+        imp.hide_shorts_row
+            .connect_active_notify(clone!(@weak self as window => move |row| {
+                let active = row.is_active();
+                window.update_filter(|filter| filter.hide_shorts = active);
+            }));
+        imp.hide_live_row
+            .connect_active_notify(clone!(@weak self as window => move |row| {
+                let active = row.is_active();
+                window.update_filter(|filter| filter.hide_live = active);
+            }));
+        imp.min_length_row
+            .connect_value_notify(clone!(@weak self as window => move |row| {
+                let length = row.value() as u32 * 60;
+                window.update_filter(|filter| filter.min_length = length);
+            }));
+        imp.max_length_row
+            .connect_value_notify(clone!(@weak self as window => move |row| {
+                let length = row.value() as u32 * 60;
+                window.update_filter(|filter| filter.max_length = length);
+            }));
+
+        // Channels can be blocked from the main window while this is open
+        self.model().settings().connect_changed(
+            Some("content-filter"),
+            clone!(@weak self as window => move |_, _| window.build_filter_lists()),
+        );
+        self.build_filter_lists();
+    }
+
+    fn update_filter(&self, update: impl FnOnce(&mut ContentFilter)) {
+        let model = self.model();
+        let mut filter = model.content_filter();
+        update(&mut filter);
+        model.set_content_filter(&filter);
+    }
+
+    fn build_filter_lists(&self) {
+        let imp = self.imp();
+        for (group, row) in imp.filter_rows.take() {
+            group.remove(&row);
+        }
+
+        let filter = self.model().content_filter();
+        let mut rows = Vec::new();
+        for keyword in filter.keywords {
+            let row = adw::ActionRow::builder()
+                .title(&keyword)
+                .use_markup(false)
+                .build();
+            let button = removal_button("Remove Keyword");
+            button.connect_clicked(clone!(@weak self as window => move |_| {
+                window.update_filter(|filter| filter.remove_keyword(&keyword));
+            }));
+            row.add_suffix(&button);
+            rows.push((imp.keywords_group.get(), row));
+        }
+        for channel in filter.blocked_channels {
+            let row = adw::ActionRow::builder()
+                .title(&channel.name)
+                .use_markup(false)
+                .build();
+            let button = removal_button("Unblock Channel");
+            button.connect_clicked(clone!(@weak self as window => move |_| {
+                window.update_filter(|filter| filter.unblock_channel(&channel.id));
+            }));
+            row.add_suffix(&button);
+            rows.push((imp.blocked_channels_group.get(), row));
+        }
+
+        for (group, row) in &rows {
+            group.add(row);
+        }
+        imp.filter_rows.replace(rows);
+    }
+
+    fn setup_profile_rows(&self) {
+        self.model().settings().connect_changed(
+            Some("active-profile"),
+            clone!(@weak self as window => move |_, _| window.build_profile_rows()),
+        );
+        self.build_profile_rows();
+    }
+
+    fn build_profile_rows(&self) {
+        let imp = self.imp();
+        let model = self.model();
+        let active = model.active_profile();
+        imp.profile_name_row.set_text(&active.name);
+        imp.profile_pin_row.set_text("");
+
+        for row in imp.profile_rows.take() {
+            imp.other_profiles_group.remove(&row);
+        }
+        let mut rows = Vec::new();
+        for profile in model.profiles().into_iter().filter(|x| x.id != active.id) {
+            let row = adw::ActionRow::builder()
+                .title(&profile.name)
+                .use_markup(false)
+                .build();
+            if profile.is_locked() {
+                let icon = gtk::Image::from_icon_name("system-lock-screen-symbolic");
+                icon.set_tooltip_text(Some("Locked with a PIN"));
+                row.add_suffix(&icon);
+            } else {
+                let button = removal_button("Remove Profile");
+                button.connect_clicked(clone!(@weak self as window => move |_| {
+                    window.confirm_remove_profile(&profile);
+                }));
+                row.add_suffix(&button);
+            }
+            imp.other_profiles_group.add(&row);
+            rows.push(row);
+        }
+        imp.other_profiles_group.set_visible(!rows.is_empty());
+        imp.profile_rows.replace(rows);
+    }
+
+    fn confirm_remove_profile(&self, profile: &Profile) {
+        let dialog = adw::MessageDialog::builder()
+            .heading(format!("Remove {}?", profile.name))
+            .body("Its subscriptions, history, playlists and filters will be deleted")
+            .modal(true)
+            .transient_for(self)
+            .build();
+        dialog.add_responses(&[("cancel", "Cancel"), ("remove", "Remove")]);
+        dialog.set_response_appearance("remove", ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+        let id = profile.id.clone();
+        dialog.connect_response(
+            Some("remove"),
+            clone!(@weak self as window => move |_, _| {
+                window.model().remove_profile(&id);
+                window.build_profile_rows();
+            }),
+        );
+        dialog.present();
+    }
+
+    fn export_backup(&self) {
+        let date = glib::DateTime::now_local()
+            .and_then(|x| x.format("%Y-%m-%d"))
+            .map(|x| x.to_string())
+            .unwrap_or_default();
+        let dialog = gtk::FileDialog::builder()
+            .title("Export Backup")
+            .initial_name(format!("pryvid-backup-{}.json", date))
+            .modal(true)
+            .build();
+        MainContext::default().spawn_local(clone!(@weak self as window => async move {
+            // Cancelling the dialog is an error too, with nothing to report
+            let Some(path) = dialog.save_future(Some(&window)).await.ok().and_then(|x| x.path()) else {
+                return;
+            };
+            let message = match fs::write(path, window.model().backup().to_json()) {
+                Ok(()) => "Backup exported".to_string(),
+                Err(error) => format!("Failed to export backup: {}", error),
+            };
+            window.add_toast(adw::Toast::builder().title(message).build());
+        }));
+    }
+
+    fn restore_backup(&self) {
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("Pryvid Backups"));
+        filter.add_pattern("*.json");
+        let filters = gio::ListStore::new::<gtk::FileFilter>();
+        filters.append(&filter);
+        let dialog = gtk::FileDialog::builder()
+            .title("Restore Backup")
+            .filters(&filters)
+            .modal(true)
+            .build();
+        MainContext::default().spawn_local(clone!(@weak self as window => async move {
+            let Some(path) = dialog.open_future(Some(&window)).await.ok().and_then(|x| x.path()) else {
+                return;
+            };
+            let backup = fs::read_to_string(path)
+                .map_err(|error| error.to_string())
+                .and_then(|json| Backup::from_json(&json).map_err(|error| error.to_string()));
+            match backup {
+                Ok(backup) => window.confirm_restore(backup),
+                Err(error) => window.add_toast(
+                    adw::Toast::builder()
+                        .title(format!("Failed to restore backup: {}", error))
+                        .build(),
+                ),
+            }
+        }));
+    }
+
+    fn confirm_restore(&self, backup: Backup) {
+        let dialog = adw::MessageDialog::builder()
+            .heading("Restore Backup?")
+            .body("Merging adds the instances and profile data from the backup to what's here now. Replacing makes everything, including settings, exactly like it was when the backup was made. Profiles with a PIN are left as they are, unless they're the current one")
+            .modal(true)
+            .transient_for(self)
+            .build();
+        dialog.add_responses(&[
+            ("cancel", "Cancel"),
+            ("merge", "Merge"),
+            ("replace", "Replace"),
+        ]);
+        dialog.set_response_appearance("replace", ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("merge"));
+        dialog.set_close_response("cancel");
+        // Responses can only happen once, but the handler has to be callable more often
+        let backup = RefCell::new(Some(backup));
+        dialog.connect_response(
+            None,
+            clone!(@weak self as window => move |_, response| {
+                let mode = match response {
+                    "merge" => RestoreMode::Merge,
+                    "replace" => RestoreMode::Replace,
+                    _ => return,
+                };
+                if let Some(backup) = backup.take() {
+                    let locked = window.model().restore(backup, mode);
+                    if let Some(id) = locked {
+                        if let Some(main_window) =
+                            window.transient_for().and_downcast::<PryvidWindow>()
+                        {
+                            main_window.ask_for_profile(&id);
+                        }
+                    }
+                    window.rebuild();
+                    window.build_profile_rows();
+                    window.add_toast(adw::Toast::builder().title("Backup restored").build());
+                }
+            }),
+        );
+        dialog.present();
+    }
+
+    fn apply_server(&self, key: &str, row: &adw::EntryRow) {
+        let server = row.text();
+        let server = server.trim().trim_end_matches('/');
+        if server.starts_with("https://") || server.starts_with("http://") {
+            self.model().settings().set_string(key, server).unwrap();
+        } else {
+            self.add_toast(
+                adw::Toast::builder()
+                    .title("Server address must start with http:// or https://")
+                    .build(),
+            );
+        }
+    }
+
+    fn build_segment_rows(&self) {
+        let actions = self.model().segment_actions();
+        let titles: Vec<&str> = SegmentAction::ALL.iter().map(|x| x.title()).collect();
+        for category in SegmentCategory::ALL {
+            let row = adw::ComboRow::builder()
+                .title(category.title())
+                .model(&gtk::StringList::new(&titles))
+                .build();
+            let action = actions
+                .get(&category)
+                .copied()
+                .unwrap_or(SegmentAction::Ignore);
+            row.set_selected(
+                SegmentAction::ALL
+                    .iter()
+                    .position(|x| *x == action)
+                    .unwrap_or_default() as u32,
+            );
+            row.connect_selected_notify(clone!(@weak self as window => move |row| {
+                if let Some(action) = SegmentAction::ALL.get(row.selected() as usize) {
+                    window.model().set_segment_action(category, *action);
+                }
+            }));
+            self.imp().sponsorblock_categories_group.add(&row);
+        }
+    }
+
+    fn update_proxy_rows(&self) {
+        let imp = self.imp();
+        let mode = PROXY_MODES.get(imp.proxy_mode_row.selected() as usize);
+        imp.proxy_uri_row.set_visible(mode == Some(&"manual"));
+        imp.tor_uri_row.set_visible(mode == Some(&"tor"));
+    }
+
+    fn show_contacted_hosts_dialog(&self) {
+        let hosts = contacted_hosts();
+        let body = if hosts.is_empty() {
+            "No requests have been made yet".to_string()
+        } else {
+            hosts
+                .iter()
+                .map(|(host, count)| format!("{host} ({count})"))
+                .collect::<Vec<String>>()
+                .join("\n")
+        };
+        let dialog = adw::MessageDialog::builder()
+            .heading("Contacted Hosts")
+            .body(body)
+            .transient_for(self)
+            .build();
+        dialog.add_responses(&[("clear", "Clear"), ("close", "Close")]);
+        dialog.set_default_response(Some("close"));
+        dialog.connect_response(Some("clear"), |_, _| clear_contacted_hosts());
+        dialog.present();
+    }
+
+    fn test_connection(&self) {
+        MainContext::default().spawn_local(clone!(@weak self as window => async move {
+            let test_button = window.imp().test_button.clone();
+            test_button.set_sensitive(false);
+
+            let instance = window.model().invidious().get_instance();
+            let mut message = match instance.ping(Some("/api/v1/stats")).await {
+                Ok(ping) => format!("Reached {} in {ping} ms", instance.uri),
+                Err(error) => format!("Could not reach {}: {error}", instance.uri),
+            };
+            if window.model().settings().string("proxy-mode").as_str() == "tor" {
+                message = match check_tor().await {
+                    Ok(true) => format!("{message} through Tor"),
+                    Ok(false) => "Warning: requests are not going through Tor".into(),
+                    Err(error) => format!("Could not verify the Tor connection: {error}"),
+                };
+            }
+
+            window.add_toast(adw::Toast::builder().title(message).build());
+            test_button.set_sensitive(true);
+        }));
+    }
+
     fn model(&self) -> Arc<AppModel> {
         self.imp().model.get().unwrap().clone()
     }
@@ -150,8 +754,7 @@ impl PryvidPreferencesWindow {
         }
     }
 
-    fn show_curation_dialog(&self, instances: Instances) {
-        let dialog = CurationWindow::new(self.model(), instances);
+    fn show_curation_dialog(&self, dialog: CurationWindow) {
         dialog.connect_destroy(clone!(@weak self as window => move |_| {
             window.rebuild()
         }));
@@ -176,13 +779,17 @@ impl PryvidPreferencesWindow {
     }
 
     fn show_manage_dialog(&self) {
-        self.show_curation_dialog(self.model().invidious().instances());
+        self.show_curation_dialog(CurationWindow::new(
+            self.model(),
+            self.model().invidious().instances(),
+        ));
     }
 
     fn show_discover_dialog(&self) {
         MainContext::default().spawn_local(clone!(@weak self as window => async move {
             let (abort_handle, abort_registration) = AbortHandle::new_pair();
-            let future = Abortable::new(fetch_instances(), abort_registration);
+            let include_hidden = window.model().settings().boolean("discover-hidden-services");
+            let future = Abortable::new(fetch_instances(include_hidden), abort_registration);
 
             let loading_window = LoadingWindow::new();
             loading_window.set_modal(true);
@@ -196,11 +803,27 @@ impl PryvidPreferencesWindow {
             if let Ok(instances) = future.await {
                 loading_window.close();
 
-                // TODO: Do better error management
-                if let Ok(instances) = instances {
-                    window.show_curation_dialog(instances);
+                match instances {
+                    Ok(instances) => window.show_curation_dialog(
+                        CurationWindow::discover(window.model(), instances)
+                    ),
+                    Err(error) => window.add_toast(
+                        adw::Toast::builder()
+                            .title(format!("Failed to fetch instances: {error}"))
+                            .build()
+                    ),
                 }
             }
         }));
     }
 }
+
+fn removal_button(tooltip: &str) -> gtk::Button {
+    let button = gtk::Button::builder()
+        .icon_name("user-trash-symbolic")
+        .tooltip_text(tooltip)
+        .valign(gtk::Align::Center)
+        .build();
+    button.add_css_class("flat");
+    button
+}
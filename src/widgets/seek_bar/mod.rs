@@ -0,0 +1,372 @@
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use glib::{clone, subclass::Signal, MainContext, Object, Properties};
+use gtk::gdk_pixbuf::{Pixbuf, PixbufLoader};
+use gtk::{gdk, glib, graphene, gsk};
+use isahc::prelude::*;
+use once_cell::sync::Lazy;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+
+use crate::api::{self, StoryboardTile};
+use crate::utils::format_duration;
+
+const TRACK_HEIGHT: f32 = 4.0;
+const HOVER_TRACK_HEIGHT: f32 = 6.0;
+const MARKER_GAP: f32 = 2.0;
+
+#[derive(Debug, Clone)]
+pub struct SeekBarMarker {
+    // Microseconds, like gtk::MediaStream timestamps
+    pub position: i64,
+    pub label: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SeekBarSegment {
+    // Microseconds
+    pub start: i64,
+    pub end: i64,
+    pub color: gdk::RGBA,
+}
+
+mod imp {
+
+    use super::*;
+
+    #[derive(Default, Debug, Properties)]
+    #[properties(wrapper_type = super::SeekBar)]
+    pub struct SeekBar {
+        #[property(get, set = Self::set_duration)]
+        pub duration: Cell<i64>,
+        #[property(get, set = Self::set_position)]
+        pub position: Cell<i64>,
+
+        pub markers: RefCell<Vec<SeekBarMarker>>,
+        pub segments: RefCell<Vec<SeekBarSegment>>,
+        pub storyboard: RefCell<Vec<StoryboardTile>>,
+        pub sheets: RefCell<HashMap<String, Pixbuf>>,
+        // Sheets that are loading or failed to load, so they are only requested once
+        pub requested_sheets: RefCell<HashSet<String>>,
+        pub hover_x: Cell<Option<f64>>,
+        pub drag_x: Cell<Option<f64>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SeekBar {
+        const NAME: &'static str = "SeekBar";
+        type Type = super::SeekBar;
+        type ParentType = gtk::Widget;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.set_css_name("seekbar");
+        }
+    }
+
+    impl ObjectImpl for SeekBar {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.obj().setup_controllers();
+        }
+
+        fn signals() -> &'static [glib::subclass::Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![Signal::builder("seek")
+                    .param_types([i64::static_type()])
+                    .build()]
+            });
+            SIGNALS.as_ref()
+        }
+
+        fn properties() -> &'static [glib::ParamSpec] {
+            Self::derived_properties()
+        }
+
+        fn set_property(&self, id: usize, value: &glib::Value, pspec: &glib::ParamSpec) {
+            self.derived_set_property(id, value, pspec)
+        }
+
+        fn property(&self, id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+            self.derived_property(id, pspec)
+        }
+    }
+
+    impl WidgetImpl for SeekBar {
+        fn measure(&self, orientation: gtk::Orientation, _for_size: i32) -> (i32, i32, i32, i32) {
+            match orientation {
+                gtk::Orientation::Horizontal => (100, 300, -1, -1),
+                _ => (16, 16, -1, -1),
+            }
+        }
+
+        fn snapshot(&self, snapshot: &gtk::Snapshot) {
+            let obj = self.obj();
+            let width = obj.width() as f32;
+            let height = obj.height() as f32;
+            if width <= 0.0 {
+                return;
+            }
+
+            let color = obj.color();
+            let track_color = gdk::RGBA::new(color.red(), color.green(), color.blue(), 0.25);
+            let hover_color = gdk::RGBA::new(color.red(), color.green(), color.blue(), 0.45);
+
+            let progress_x = width * obj.fraction_at(self.drag_x.get());
+            let hover_x = self.hover_x.get().map(|x| x as f32);
+
+            // The track is split into one piece per marker, with a small gap in between
+            let mut boundaries = vec![0.0];
+            boundaries.extend(
+                self.markers
+                    .borrow()
+                    .iter()
+                    .map(|x| width * obj.fraction_of(x.position))
+                    .filter(|x| *x > 0.0 && *x < width),
+            );
+            boundaries.push(width);
+
+            for piece in boundaries.windows(2) {
+                let start = if piece[0] > 0.0 {
+                    piece[0] + MARKER_GAP / 2.0
+                } else {
+                    0.0
+                };
+                let end = if piece[1] < width {
+                    piece[1] - MARKER_GAP / 2.0
+                } else {
+                    width
+                };
+                if end <= start {
+                    continue;
+                }
+                let hovered = hover_x.is_some_and(|x| x >= piece[0] && x < piece[1]);
+                let track_height = if hovered {
+                    HOVER_TRACK_HEIGHT
+                } else {
+                    TRACK_HEIGHT
+                };
+                let y = (height - track_height) / 2.0;
+
+                let rect = || graphene::Rect::new(start, y, end - start, track_height);
+                snapshot
+                    .push_rounded_clip(&gsk::RoundedRect::from_rect(rect(), track_height / 2.0));
+                snapshot.append_color(&track_color, &rect());
+                if let Some(hover_x) = hover_x.filter(|_| hovered) {
+                    snapshot.append_color(
+                        &hover_color,
+                        &graphene::Rect::new(start, y, (hover_x - start).max(0.0), track_height),
+                    );
+                }
+                if progress_x > start {
+                    snapshot.append_color(
+                        &color,
+                        &graphene::Rect::new(start, y, progress_x.min(end) - start, track_height),
+                    );
+                }
+                for segment in self.segments.borrow().iter() {
+                    let segment_start = width * obj.fraction_of(segment.start);
+                    let segment_end = width * obj.fraction_of(segment.end);
+                    if segment_end > start && segment_start < end {
+                        snapshot.append_color(
+                            &segment.color,
+                            &graphene::Rect::new(
+                                segment_start,
+                                y,
+                                segment_end - segment_start,
+                                track_height,
+                            ),
+                        );
+                    }
+                }
+                snapshot.pop();
+            }
+        }
+    }
+
+    impl SeekBar {
+        fn set_duration(&self, duration: i64) {
+            self.duration.set(duration);
+            self.obj().queue_draw();
+        }
+
+        fn set_position(&self, position: i64) {
+            self.position.set(position);
+            self.obj().queue_draw();
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct SeekBar(ObjectSubclass<imp::SeekBar>)
+        @extends gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl Default for SeekBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SeekBar {
+    pub fn new() -> Self {
+        Object::builder().build()
+    }
+
+    pub fn set_markers(&self, markers: Vec<SeekBarMarker>) {
+        self.imp().markers.replace(markers);
+        self.queue_draw();
+    }
+
+    pub fn set_segments(&self, segments: Vec<SeekBarSegment>) {
+        self.imp().segments.replace(segments);
+        self.queue_draw();
+    }
+
+    pub fn set_storyboard(&self, tiles: Vec<StoryboardTile>) {
+        self.imp().storyboard.replace(tiles);
+    }
+
+    /// Preview image for a position, starting to load its sprite sheet if needed
+    fn storyboard_preview(&self, position: i64) -> Option<gdk::Texture> {
+        let imp = self.imp();
+        let seconds = position as f64 / 1_000_000.0;
+        let tile = imp
+            .storyboard
+            .borrow()
+            .iter()
+            .find(|x| x.start <= seconds && seconds < x.end)
+            .cloned()?;
+        let Some(sheet) = imp.sheets.borrow().get(&tile.sheet_uri).cloned() else {
+            self.load_sheet(tile.sheet_uri);
+            return None;
+        };
+
+        // The last tiles of a sheet can be cut off
+        let width = tile.width.min(sheet.width() - tile.x);
+        let height = tile.height.min(sheet.height() - tile.y);
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+        let preview = sheet.new_subpixbuf(tile.x, tile.y, width, height);
+        Some(gdk::Texture::for_pixbuf(&preview))
+    }
+
+    fn load_sheet(&self, uri: String) {
+        if !self.imp().requested_sheets.borrow_mut().insert(uri.clone()) {
+            return;
+        }
+        MainContext::default().spawn_local(clone!(@weak self as obj => async move {
+            match fetch_sheet(&uri).await {
+                Some(sheet) => {
+                    obj.imp().sheets.borrow_mut().insert(uri, sheet);
+                    obj.trigger_tooltip_query();
+                }
+                None => log::warn!("Failed to load storyboard sheet {}", uri),
+            }
+        }));
+    }
+
+    /// Fraction of the bar that is filled, using `drag_x` if the user is dragging
+    fn fraction_at(&self, drag_x: Option<f64>) -> f32 {
+        if let Some(x) = drag_x {
+            (x as f32 / self.width().max(1) as f32).clamp(0.0, 1.0)
+        } else {
+            self.fraction_of(self.position())
+        }
+    }
+
+    fn fraction_of(&self, position: i64) -> f32 {
+        if self.duration() <= 0 {
+            0.0
+        } else {
+            (position as f64 / self.duration() as f64).clamp(0.0, 1.0) as f32
+        }
+    }
+
+    fn position_at(&self, x: f64) -> i64 {
+        let fraction = (x / self.width().max(1) as f64).clamp(0.0, 1.0);
+        (fraction * self.duration() as f64) as i64
+    }
+
+    fn marker_label_at(&self, position: i64) -> Option<String> {
+        self.imp()
+            .markers
+            .borrow()
+            .iter()
+            .take_while(|x| x.position <= position)
+            .last()
+            .map(|x| x.label.clone())
+    }
+
+    fn setup_controllers(&self) {
+        let motion = gtk::EventControllerMotion::new();
+        motion.connect_motion(clone!(@weak self as obj => move |_, x, _| {
+            obj.imp().hover_x.set(Some(x));
+            obj.queue_draw();
+        }));
+        motion.connect_leave(clone!(@weak self as obj => move |_| {
+            obj.imp().hover_x.set(None);
+            obj.queue_draw();
+        }));
+        self.add_controller(motion);
+
+        // Only seek once the user lets go, seeking a network stream is expensive
+        let drag = gtk::GestureDrag::new();
+        drag.connect_drag_begin(clone!(@weak self as obj => move |_, x, _| {
+            obj.imp().drag_x.set(Some(x));
+            obj.queue_draw();
+        }));
+        drag.connect_drag_update(clone!(@weak self as obj => move |gesture, offset_x, _| {
+            if let Some((start_x, _)) = gesture.start_point() {
+                obj.imp().drag_x.set(Some(start_x + offset_x));
+                obj.queue_draw();
+            }
+        }));
+        drag.connect_drag_end(clone!(@weak self as obj => move |_, _, _| {
+            if let Some(x) = obj.imp().drag_x.take() {
+                let position = obj.position_at(x);
+                obj.set_position(position);
+                obj.emit_by_name::<()>("seek", &[&position]);
+            }
+        }));
+        self.add_controller(drag);
+
+        self.set_has_tooltip(true);
+        self.connect_query_tooltip(|obj, x, _, _, tooltip| {
+            if obj.duration() <= 0 {
+                return false;
+            }
+            let position = obj.position_at(x as f64);
+            let timestamp = format_duration((position / 1_000_000) as u32);
+            let text = match obj.marker_label_at(position) {
+                Some(label) => format!("{timestamp} • {label}"),
+                None => timestamp,
+            };
+            match obj.storyboard_preview(position) {
+                Some(preview) => {
+                    let content = gtk::Box::new(gtk::Orientation::Vertical, 6);
+                    let picture = gtk::Picture::for_paintable(&preview);
+                    picture.set_can_shrink(false);
+                    content.append(&picture);
+                    content.append(&gtk::Label::new(Some(&text)));
+                    tooltip.set_custom(Some(&content));
+                }
+                None => tooltip.set_text(Some(&text)),
+            }
+            true
+        });
+    }
+}
+
+async fn fetch_sheet(uri: &str) -> Option<Pixbuf> {
+    let mut response = api::get(uri).await.ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    let data = response.bytes().await.ok()?;
+    let loader = PixbufLoader::new();
+    loader.write(&data).ok()?;
+    loader.close().ok()?;
+    loader.pixbuf()
+}
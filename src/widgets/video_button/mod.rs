@@ -1,12 +1,14 @@
 use adw::prelude::*;
 use adw::subclass::prelude::*;
-use glib::{Object, Properties};
-use gtk::glib;
+use glib::{clone, MainContext, Object, Properties};
 use gtk::CompositeTemplate;
-use std::cell::RefCell;
+use gtk::{gdk, gio, glib};
+use std::cell::{Cell, RefCell};
 
+use crate::api::dearrow;
 use crate::api::Video;
-use crate::utils::format_number_magnitude;
+use crate::appmodel::AppModel;
+use crate::utils::{format_duration, format_number_magnitude};
 use crate::widgets::async_image::AsyncImage;
 
 mod imp {
@@ -18,9 +20,9 @@ mod imp {
     #[template(resource = "/dev/quark97/Pryvid/video_button.ui")]
     pub struct VideoButton {
         #[template_child]
-        thumbnail_image: TemplateChild<AsyncImage>,
+        pub thumbnail_image: TemplateChild<AsyncImage>,
         #[template_child]
-        title_label: TemplateChild<gtk::Label>,
+        pub title_label: TemplateChild<gtk::Label>,
         #[template_child]
         author_button: TemplateChild<gtk::Button>,
         #[template_child]
@@ -46,6 +48,8 @@ mod imp {
         author_id: RefCell<String>,
         #[property(get, set)]
         video_id: RefCell<String>,
+        #[property(get, set)]
+        live: Cell<bool>,
     }
 
     #[glib::object_subclass]
@@ -64,6 +68,11 @@ mod imp {
     }
 
     impl ObjectImpl for VideoButton {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.obj().setup_context_menu();
+        }
+
         fn properties() -> &'static [glib::ParamSpec] {
             Self::derived_properties()
         }
@@ -85,14 +94,7 @@ mod imp {
             let string = if number == 0 {
                 String::new()
             } else {
-                let seconds = number % 60;
-                let minutes = (number / 60) % 60;
-                let hours = (number / 60) / 60;
-                if hours > 0 {
-                    format!("{:0>2}:{:0>2}:{:0>2}", hours, minutes, seconds)
-                } else {
-                    format!("{:0>2}:{:0>2}", minutes, seconds)
-                }
+                format_duration(number)
             };
             self.length_label.set_visible(string.len() > 0);
             self.length_label.set_text(&string);
@@ -129,7 +131,7 @@ glib::wrapper! {
 }
 
 impl VideoButton {
-    pub fn new(video: &Video) -> Self {
+    pub fn new(video: &Video, model: &AppModel) -> Self {
         let thumbnail_uri = video
             .thumbnails
             .iter()
@@ -137,7 +139,7 @@ impl VideoButton {
             .find(|(_, q)| q == &"medium")
             .map(|(x, _)| x.uri.clone());
 
-        Object::builder()
+        let obj: Self = Object::builder()
             .property("thumbnail", thumbnail_uri.unwrap_or_default())
             .property("title", &video.title)
             .property("author", &video.author)
@@ -146,6 +148,87 @@ impl VideoButton {
             .property("views", video.views)
             .property("author-id", &video.author_id)
             .property("video-id", &video.id)
-            .build()
+            .property("live", video.live)
+            .build();
+        obj.fetch_branding(model);
+        obj
+    }
+
+    fn setup_context_menu(&self) {
+        let click = gtk::GestureClick::builder()
+            .button(gdk::BUTTON_SECONDARY)
+            .build();
+        click.connect_pressed(clone!(@weak self as obj => move |gesture, _, x, y| {
+            gesture.set_state(gtk::EventSequenceState::Claimed);
+            obj.show_context_menu(x, y);
+        }));
+        self.add_controller(click);
+
+        let long_press = gtk::GestureLongPress::builder().touch_only(true).build();
+        long_press.connect_pressed(clone!(@weak self as obj => move |gesture, x, y| {
+            gesture.set_state(gtk::EventSequenceState::Claimed);
+            obj.show_context_menu(x, y);
+        }));
+        self.add_controller(long_press);
+    }
+
+    fn show_context_menu(&self, x: f64, y: f64) {
+        let menu = gio::Menu::new();
+        let radio_item = gio::MenuItem::new(Some("Start Radio"), None);
+        radio_item.set_action_and_target_value(
+            Some("win.start-radio"),
+            Some(&self.video_id().to_variant()),
+        );
+        menu.append_item(&radio_item);
+        let block_item = gio::MenuItem::new(Some("Block Channel"), None);
+        block_item.set_action_and_target_value(
+            Some("win.block-channel"),
+            Some(&(self.author_id(), self.author()).to_variant()),
+        );
+        menu.append_item(&block_item);
+
+        let popover = gtk::PopoverMenu::from_model(Some(&menu));
+        popover.set_parent(self);
+        popover.set_has_arrow(false);
+        popover.set_halign(gtk::Align::Start);
+        popover.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+        // The menu is built again every time, so get rid of this one once it's done
+        popover.connect_closed(|popover| {
+            glib::idle_add_local_once(clone!(@weak popover => move || popover.unparent()));
+        });
+        popover.popup();
+    }
+
+    fn fetch_branding(&self, model: &AppModel) {
+        let settings = model.settings();
+        if !settings.boolean("dearrow-enabled") {
+            return;
+        }
+        let server = settings.string("dearrow-server").to_string();
+        // Unlike titles, thumbnails can't be looked up by hash prefix, so they're only
+        // replaced when the user agreed to send the thumbnail server every video ID
+        let thumbnail_server = Some(settings.string("dearrow-thumbnail-server").to_string())
+            .filter(|_| settings.boolean("dearrow-thumbnails"));
+
+        MainContext::default().spawn_local(clone!(@weak self as obj => async move {
+            match dearrow::branding(&server, &obj.video_id()).await {
+                Ok(branding) => {
+                    let imp = obj.imp();
+                    if let Some(title) = branding.title {
+                        imp.title_label
+                            .set_tooltip_text(Some(&format!("Original title: {}", obj.title())));
+                        obj.set_title(title);
+                    }
+                    if let (Some(time), Some(thumbnail_server)) =
+                        (branding.thumbnail_time, &thumbnail_server)
+                    {
+                        // The thumbnail server may not have rendered this one yet
+                        imp.thumbnail_image.set_fallback_uri(obj.thumbnail());
+                        obj.set_thumbnail(dearrow::thumbnail_uri(thumbnail_server, &obj.video_id(), time));
+                    }
+                }
+                Err(error) => log::warn!("Failed to fetch DeArrow branding: {}", error),
+            }
+        }));
     }
 }
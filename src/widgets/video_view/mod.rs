@@ -1,22 +1,39 @@
 use adw::prelude::*;
 use adw::subclass::prelude::*;
-use glib::{clone, MainContext, Object, Properties};
+use glib::{clone, closure_local, subclass::Signal, MainContext, Object, Properties};
 use gtk::CompositeTemplate;
-use gtk::{gio, glib};
-use once_cell::sync::OnceCell;
+use gtk::{gdk, gio, glib};
+use once_cell::sync::{Lazy, OnceCell};
 use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::api::DetailedVideo;
+use crate::api::dearrow::{self, Branding};
+use crate::api::dislikes::votes;
+use crate::api::sponsorblock::{skip_segments, Segment, SegmentAction, SegmentCategory};
+use crate::api::{record_host, Chapter, DetailedVideo, Instance};
 use crate::appmodel::AppModel;
-use crate::utils::format_number_magnitude;
+use crate::markup::{html_to_pango, link_hashtags, parse_link, Link};
+use crate::player::{speed_index, PlayerStream, SPEEDS};
+use crate::utils::{format_duration, format_number_magnitude};
 use crate::widgets::{
     async_image::AsyncImage,
     content_grid::ContentGrid,
     instance_indicator::InstanceIndicator,
     result_page::{ResultPage, ResultPageState},
+    seek_bar::{SeekBar, SeekBarMarker, SeekBarSegment},
 };
 
+// How long the fullscreen seek bar stays visible after the pointer stops moving
+const SEEK_BAR_HIDE_DELAY: Duration = Duration::from_secs(3);
+// Going to the previous chapter restarts the current one if we are further in than this
+const CHAPTER_RESTART_THRESHOLD: i64 = 3;
+// Seconds between checks for whether a premiere that is due has started
+const PREMIERE_POLL_INTERVAL: u32 = 30;
+// Used for frame stepping when the stream doesn't say
+const DEFAULT_FPS: u32 = 30;
+
 mod imp {
 
     use super::*;
@@ -25,8 +42,17 @@ mod imp {
     #[properties(wrapper_type = super::VideoView)]
     #[template(resource = "/dev/quark97/Pryvid/video_view.ui")]
     pub struct VideoView {
-        pub video: OnceCell<DetailedVideo>,
+        pub video: RefCell<Option<DetailedVideo>>,
         pub model: OnceCell<Arc<AppModel>>,
+        pub chapters: RefCell<Vec<Chapter>>,
+        pub current_chapter: Cell<Option<usize>>,
+        pub seek_bar_timeout: RefCell<Option<glib::SourceId>>,
+        pub segments: RefCell<Vec<(SegmentAction, Segment)>>,
+        // Segments are only skipped automatically once, so users can seek back into them
+        pub skipped_segments: RefCell<HashSet<String>>,
+        pub skip_target: Cell<i64>,
+        pub countdown: RefCell<Option<glib::SourceId>>,
+        pub branding: RefCell<Option<Branding>>,
 
         #[property(get, set)]
         pub show_sidebar: Cell<bool>,
@@ -37,8 +63,24 @@ mod imp {
         #[property(get, set)]
         pub timestamp: Cell<i64>,
         #[property(get, set)]
+        pub duration: Cell<i64>,
+        #[property(get, set)]
         pub video_id: RefCell<String>,
+        #[property(get, set)]
+        pub skip_label: RefCell<String>,
+        #[property(get, set)]
+        pub can_skip: Cell<bool>,
+        #[property(get, set = Self::set_rate, minimum = 0.25, maximum = 3.0, default = 1.0)]
+        pub rate: Cell<f64>,
 
+        #[template_child]
+        pub normal_speed_dropdown: TemplateChild<gtk::DropDown>,
+        #[template_child]
+        pub playlist_button: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub playlist_menu: TemplateChild<gio::Menu>,
+        #[template_child]
+        pub fullscreen_speed_dropdown: TemplateChild<gtk::DropDown>,
         #[template_child]
         pub instance_indicator: TemplateChild<InstanceIndicator>,
         #[template_child]
@@ -55,6 +97,14 @@ mod imp {
         pub hover_box: TemplateChild<gtk::Box>,
         #[template_child]
         pub headerbar_revealer: TemplateChild<gtk::Revealer>,
+        #[template_child]
+        pub fullscreen_overlay: TemplateChild<gtk::Overlay>,
+        #[template_child]
+        pub seek_revealer: TemplateChild<gtk::Revealer>,
+        #[template_child]
+        pub normal_seek_bar: TemplateChild<SeekBar>,
+        #[template_child]
+        pub fullscreen_seek_bar: TemplateChild<SeekBar>,
 
         #[template_child]
         pub author_thumbnail: TemplateChild<AsyncImage>,
@@ -67,13 +117,27 @@ mod imp {
         #[template_child]
         pub dislikes_label: TemplateChild<gtk::Label>,
         #[template_child]
+        pub dislikes_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub rating_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub original_title_button: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
         pub views_label: TemplateChild<gtk::Label>,
         #[template_child]
         pub published_label: TemplateChild<gtk::Label>,
         #[template_child]
+        pub title_label: TemplateChild<gtk::Label>,
+        #[template_child]
         pub description_label: TemplateChild<gtk::Label>,
         #[template_child]
         pub recommended_grid: TemplateChild<ContentGrid>,
+        #[template_child]
+        pub sidebar_stack: TemplateChild<adw::ViewStack>,
+        #[template_child]
+        pub chapters_page: TemplateChild<adw::ViewStackPage>,
+        #[template_child]
+        pub chapters_listbox: TemplateChild<gtk::ListBox>,
     }
 
     #[glib::object_subclass]
@@ -94,7 +158,29 @@ mod imp {
     impl ObjectImpl for VideoView {
         fn constructed(&self) {
             self.parent_constructed();
-            self.obj().create_hover_controller();
+            let obj = self.obj();
+            obj.create_hover_controller();
+            obj.create_key_controller();
+            obj.setup_chapters();
+            obj.setup_description();
+            obj.setup_speed();
+            obj.setup_playlist_menu();
+        }
+
+        fn signals() -> &'static [glib::subclass::Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![
+                    // Emitted when the page is left while playing, so the stream can keep
+                    // going elsewhere
+                    Signal::builder("minimize").build(),
+                    Signal::builder("ended").build(),
+                    // Messages for the user, the page may not be in the window while playing
+                    Signal::builder("toast")
+                        .param_types([String::static_type()])
+                        .build(),
+                ]
+            });
+            SIGNALS.as_ref()
         }
 
         fn properties() -> &'static [glib::ParamSpec] {
@@ -117,14 +203,22 @@ mod imp {
             if obj.sidebar_collapsed() {
                 obj.set_show_sidebar(false);
             }
-            obj.set_playing(false);
             obj.set_fullscreened(false);
         }
         fn hidden(&self) {
-            self.obj().destroy_media_stream();
+            let obj = self.obj();
+            if obj.playing() {
+                obj.emit_by_name::<()>("minimize", &[]);
+            } else {
+                obj.destroy_media_stream();
+            }
         }
         fn showing(&self) {
-            self.obj().init_media_stream();
+            // Coming back from the mini player keeps the stream that is already playing
+            let obj = self.obj();
+            if obj.media_stream().is_none() {
+                obj.init_media_stream();
+            }
         }
         fn shown(&self) {
             self.obj().set_playing(true);
@@ -138,12 +232,26 @@ mod imp {
             self.fullscreen_stack
                 .set_visible_child_name(if fullscreened { "fullscreen" } else { "normal" });
         }
+        fn set_rate(&self, rate: f64) {
+            self.rate.set(rate);
+            if let Some(stream) = self
+                .normal_video_widget
+                .media_stream()
+                .and_downcast::<PlayerStream>()
+            {
+                stream.set_rate(rate);
+            }
+            // Remembered per channel, so e.g. podcasts can always play faster
+            if let Some(video) = self.video.borrow().as_ref() {
+                self.obj().model().set_channel_speed(&video.author_id, rate);
+            }
+        }
         #[template_callback]
         fn on_channel_clicked(&self) {
             self.obj()
                 .activate_action(
                     "win.open-channel",
-                    Some(&self.video.get().unwrap().author_id.to_variant()),
+                    Some(&self.video.borrow().as_ref().unwrap().author_id.to_variant()),
                 )
                 .unwrap();
         }
@@ -152,6 +260,29 @@ mod imp {
             self.obj().set_show_sidebar(false);
         }
         #[template_callback]
+        fn on_original_title_toggled(&self, _: gtk::ToggleButton) {
+            self.obj().update_title();
+        }
+        #[template_callback]
+        fn on_skip_segment_clicked(&self, _: gtk::Button) {
+            self.obj().seek(self.skip_target.get());
+        }
+        #[template_callback]
+        fn on_listen_button_clicked(&self, _: gtk::Button) {
+            let obj = self.obj();
+            obj.activate_action(
+                "win.play-audio",
+                Some(&(obj.video_id(), obj.timestamp()).to_variant()),
+            )
+            .unwrap();
+        }
+        #[template_callback]
+        fn on_radio_button_clicked(&self, _: gtk::Button) {
+            let obj = self.obj();
+            obj.activate_action("win.start-radio", Some(&obj.video_id().to_variant()))
+                .unwrap();
+        }
+        #[template_callback]
         fn on_refresh_clicked(&self, _: ResultPage) {
             self.obj().fetch_video();
         }
@@ -167,6 +298,7 @@ glib::wrapper! {
 impl VideoView {
     pub fn new(model: Arc<AppModel>, video_id: String) -> Self {
         let obj: VideoView = Object::builder().build();
+        obj.imp().recommended_grid.set_model(model.clone());
         obj.imp().model.set(model).unwrap();
         obj.set_video_id(video_id);
         obj.fetch_video();
@@ -177,6 +309,14 @@ impl VideoView {
         self.imp().model.get().unwrap().clone()
     }
 
+    pub fn video(&self) -> Option<DetailedVideo> {
+        self.imp().video.borrow().clone()
+    }
+
+    pub fn media_stream(&self) -> Option<gtk::MediaStream> {
+        self.imp().normal_video_widget.media_stream()
+    }
+
     pub fn set_playing(&self, playing: bool) {
         if let Some(stream) = self.imp().normal_video_widget.media_stream() {
             stream.set_playing(playing);
@@ -193,10 +333,18 @@ impl VideoView {
 
     pub fn init_media_stream(&self) {
         let imp = self.imp();
-        if let Some(video) = imp.video.get() {
-            let uri = &video.format_streams.last().unwrap().uri;
-            let file = gio::File::for_uri(uri);
-            let stream = gtk::MediaFile::for_file(&file);
+        let uri = imp
+            .video
+            .borrow()
+            .as_ref()
+            .and_then(|x| x.stream_uri().map(str::to_string));
+        if let Some(uri) = uri {
+            record_host(&uri);
+            let stream = PlayerStream::for_uri(&uri);
+            stream.set_rate(self.rate());
+            let settings = self.model().settings();
+            settings.bind("volume", &stream, "volume").build();
+            settings.bind("muted", &stream, "muted").build();
             imp.normal_video_widget.set_media_stream(Some(&stream));
             imp.fullscreen_video_widget.set_media_stream(Some(&stream));
             stream.connect_prepared_notify(clone!(@weak self as obj => move |stream| {
@@ -205,8 +353,17 @@ impl VideoView {
                     .bind_property::<Self>("timestamp", obj.as_ref(), "timestamp")
                     .sync_create()
                     .build();
+                stream
+                    .bind_property::<Self>("duration", obj.as_ref(), "duration")
+                    .sync_create()
+                    .build();
                 stream.play();
             }));
+            stream.connect_ended_notify(clone!(@weak self as obj => move |stream| {
+                if stream.is_ended() {
+                    obj.emit_by_name::<()>("ended", &[]);
+                }
+            }));
         }
     }
 
@@ -218,6 +375,486 @@ impl VideoView {
             .set_media_stream(None::<&gtk::MediaStream>);
     }
 
+    /// Steps through the speeds offered in the speed menu
+    pub fn change_speed(&self, faster: bool) {
+        let index = speed_index(self.rate());
+        let index = if faster {
+            (index + 1).min(SPEEDS.len() - 1)
+        } else {
+            index.saturating_sub(1)
+        };
+        self.set_rate(SPEEDS[index]);
+    }
+
+    /// Playlists can be added or removed from the library, so the menu is refreshed every
+    /// time it opens
+    fn setup_playlist_menu(&self) {
+        self.imp().playlist_button.connect_active_notify(
+            clone!(@weak self as obj => move |button| {
+                if button.is_active() {
+                    obj.update_playlist_menu();
+                }
+            }),
+        );
+    }
+
+    fn update_playlist_menu(&self) {
+        let imp = self.imp();
+        let playlists = gio::Menu::new();
+        for playlist in self.model().playlists() {
+            let item = gio::MenuItem::new(Some(&playlist.name), None);
+            item.set_action_and_target_value(
+                Some("win.add-to-playlist"),
+                Some(&playlist.id.to_variant()),
+            );
+            playlists.append_item(&item);
+        }
+        imp.playlist_menu.remove_all();
+        imp.playlist_menu.append_section(None, &playlists);
+        imp.playlist_menu
+            .append(Some("New Playlist…"), Some("win.new-playlist"));
+    }
+
+    fn setup_speed(&self) {
+        let imp = self.imp();
+        let labels: Vec<String> = SPEEDS.iter().map(|x| format!("{}×", x)).collect();
+        let model = gtk::StringList::new(&labels.iter().map(String::as_str).collect::<Vec<_>>());
+        for dropdown in [&*imp.normal_speed_dropdown, &*imp.fullscreen_speed_dropdown] {
+            dropdown.set_model(Some(&model));
+            self.bind_property::<gtk::DropDown>("rate", dropdown, "selected")
+                .transform_to(|_, rate: f64| Some(speed_index(rate) as u32))
+                .transform_from(|_, index: u32| SPEEDS.get(index as usize).copied())
+                .sync_create()
+                .bidirectional()
+                .build();
+        }
+    }
+
+    pub fn seek(&self, timestamp: i64) {
+        if let Some(stream) = self.imp().normal_video_widget.media_stream() {
+            if !stream.is_seekable() {
+                return;
+            }
+            let duration = stream.duration();
+            let timestamp = if duration > 0 {
+                timestamp.clamp(0, duration)
+            } else {
+                timestamp.max(0)
+            };
+            stream.seek(timestamp);
+        }
+    }
+
+    /// Seeks relative to the current position, `seconds` may be negative
+    pub fn seek_by(&self, seconds: i64) {
+        self.seek(self.timestamp() + seconds * 1_000_000);
+    }
+
+    /// Seeks to a fraction of the video, like the number keys on YouTube
+    pub fn seek_fraction(&self, fraction: f64) {
+        self.seek((self.duration() as f64 * fraction) as i64);
+    }
+
+    /// Pauses and moves a single frame forwards or backwards
+    pub fn step_frame(&self, forward: bool) {
+        let fps = self
+            .imp()
+            .video
+            .borrow()
+            .as_ref()
+            .and_then(|x| x.format_streams.last().map(|x| x.fps))
+            .filter(|fps| *fps > 0)
+            .unwrap_or(DEFAULT_FPS);
+        let frame = 1_000_000 / fps as i64;
+        self.set_playing(false);
+        if let Some(stream) = self
+            .media_stream()
+            .filter(|x| x.is_seekable())
+            .and_downcast::<PlayerStream>()
+        {
+            stream.step_frame(forward, frame);
+        }
+    }
+
+    pub fn toggle_playing(&self) {
+        self.set_playing(!self.playing());
+    }
+
+    pub fn change_volume(&self, change: f64) {
+        if let Some(stream) = self.media_stream() {
+            stream.set_muted(false);
+            stream.set_volume((stream.volume() + change).clamp(0.0, 1.0));
+        }
+    }
+
+    pub fn toggle_muted(&self) {
+        if let Some(stream) = self.media_stream() {
+            stream.set_muted(!stream.is_muted());
+        }
+    }
+
+    /// Shows or hides captions, preferring the user's language. Returns the label of the
+    /// captions that are now shown
+    pub fn toggle_captions(&self) -> Option<String> {
+        let stream = self.media_stream().and_downcast::<PlayerStream>()?;
+        if stream.subtitle_uri().is_some() {
+            stream.set_subtitle_uri(None);
+            return None;
+        }
+
+        let video = self.imp().video.borrow();
+        let captions = &video.as_ref()?.captions;
+        let languages: Vec<String> = glib::language_names()
+            .iter()
+            .filter_map(|x| {
+                x.split(|c| matches!(c, '_' | '.' | '@'))
+                    .next()
+                    .map(str::to_string)
+            })
+            .collect();
+        let caption = languages
+            .iter()
+            .find_map(|language| captions.iter().find(|x| &x.language_code == language))
+            .or(captions.first())?;
+        record_host(&caption.uri);
+        stream.set_subtitle_uri(Some(&caption.uri));
+        Some(caption.label.clone())
+    }
+
+    /// Opens the first recommended video
+    pub fn play_next(&self) {
+        let next = self
+            .imp()
+            .video
+            .borrow()
+            .as_ref()
+            .and_then(|x| x.recommended.first().map(|x| x.id.clone()));
+        if let Some(id) = next {
+            self.activate_action("win.open-video", Some(&id.to_variant()))
+                .unwrap();
+        }
+    }
+
+    pub fn seek_chapter(&self, forward: bool) {
+        let position = self.timestamp() / 1_000_000;
+        let target = {
+            let chapters = self.imp().chapters.borrow();
+            if forward {
+                chapters
+                    .iter()
+                    .find(|chapter| chapter.start as i64 > position)
+                    .map(|chapter| chapter.start)
+            } else {
+                chapters
+                    .iter()
+                    .rev()
+                    .find(|chapter| (chapter.start as i64) < position - CHAPTER_RESTART_THRESHOLD)
+                    .map(|chapter| chapter.start)
+            }
+        };
+        if let Some(start) = target {
+            self.seek(start as i64 * 1_000_000);
+        }
+    }
+
+    fn setup_description(&self) {
+        let imp = self.imp();
+        for label in [&*imp.title_label, &*imp.description_label] {
+            label.connect_activate_link(
+                clone!(@weak self as obj => @default-return glib::Propagation::Proceed, move |_, uri| {
+                    obj.open_link(uri);
+                    glib::Propagation::Stop
+                }),
+            );
+        }
+        self.connect_title_notify(|obj| {
+            obj.imp()
+                .title_label
+                .set_markup(&link_hashtags(&obj.title()));
+        });
+    }
+
+    fn open_link(&self, uri: &str) {
+        let result = match parse_link(uri) {
+            // Timestamps link back to the video we are already watching
+            Link::Video { id, start } if id == self.video_id() => {
+                self.seek(start.unwrap_or(0) as i64 * 1_000_000);
+                Ok(())
+            }
+            Link::Hashtag(tag) => self.activate_action("win.open-hashtag", Some(&tag.to_variant())),
+            _ => self.activate_action("win.open-link", Some(&uri.to_variant())),
+        };
+        if let Err(error) = result {
+            log::warn!("Failed to open {}: {}", uri, error);
+        }
+    }
+
+    fn setup_chapters(&self) {
+        let imp = self.imp();
+        imp.chapters_listbox
+            .connect_row_activated(clone!(@weak self as obj => move |_, row| {
+                let start = obj
+                    .imp()
+                    .chapters
+                    .borrow()
+                    .get(row.index() as usize)
+                    .map(|chapter| chapter.start);
+                if let Some(start) = start {
+                    obj.seek(start as i64 * 1_000_000);
+                }
+            }));
+        for seek_bar in [&*imp.normal_seek_bar, &*imp.fullscreen_seek_bar] {
+            seek_bar.connect_closure(
+                "seek",
+                false,
+                closure_local!(@watch self as obj => move |_: SeekBar, timestamp: i64| {
+                    obj.seek(timestamp);
+                }),
+            );
+        }
+        self.connect_timestamp_notify(|obj| {
+            obj.update_current_chapter();
+            obj.update_segments();
+        });
+    }
+
+    fn set_chapters(&self, chapters: Vec<Chapter>) {
+        let imp = self.imp();
+
+        imp.chapters_listbox.remove_all();
+        for chapter in &chapters {
+            let row = adw::ActionRow::builder()
+                .title(&chapter.title)
+                .subtitle(format_duration(chapter.start))
+                .use_markup(false)
+                .activatable(true)
+                .build();
+            imp.chapters_listbox.append(&row);
+        }
+        imp.chapters_page.set_visible(!chapters.is_empty());
+
+        let markers: Vec<SeekBarMarker> = chapters
+            .iter()
+            .map(|chapter| SeekBarMarker {
+                position: chapter.start as i64 * 1_000_000,
+                label: chapter.title.clone(),
+            })
+            .collect();
+        imp.normal_seek_bar.set_markers(markers.clone());
+        imp.fullscreen_seek_bar.set_markers(markers);
+
+        imp.chapters.replace(chapters);
+        imp.current_chapter.set(None);
+        self.update_current_chapter();
+    }
+
+    fn update_current_chapter(&self) {
+        let imp = self.imp();
+        let position = self.timestamp() / 1_000_000;
+        let current = imp
+            .chapters
+            .borrow()
+            .iter()
+            .rposition(|chapter| chapter.start as i64 <= position);
+        if current == imp.current_chapter.get() {
+            return;
+        }
+        imp.current_chapter.set(current);
+        match current.and_then(|index| imp.chapters_listbox.row_at_index(index as i32)) {
+            Some(row) => imp.chapters_listbox.select_row(Some(&row)),
+            None => imp.chapters_listbox.unselect_all(),
+        }
+    }
+
+    fn fetch_storyboard(&self, instance: Arc<Instance>, video: &DetailedVideo) {
+        // Use the sharpest previews available
+        let Some(info) = video.storyboards.iter().max_by_key(|x| x.width).cloned() else {
+            return;
+        };
+
+        MainContext::default().spawn_local(clone!(@weak self as obj => async move {
+            match instance.storyboard(&info).await {
+                Ok(tiles) => {
+                    let imp = obj.imp();
+                    imp.normal_seek_bar.set_storyboard(tiles.clone());
+                    imp.fullscreen_seek_bar.set_storyboard(tiles);
+                }
+                Err(error) => log::warn!("Failed to fetch storyboard: {}", error),
+            }
+        }));
+    }
+
+    fn fetch_branding(&self) {
+        let settings = self.model().settings();
+        if !settings.boolean("dearrow-enabled") {
+            return;
+        }
+        let server = settings.string("dearrow-server").to_string();
+
+        MainContext::default().spawn_local(clone!(@weak self as obj => async move {
+            match dearrow::branding(&server, &obj.video_id()).await {
+                Ok(branding) if branding.title.is_some() => {
+                    obj.imp().original_title_button.set_visible(true);
+                    obj.imp().branding.replace(Some(branding));
+                    obj.update_title();
+                }
+                Ok(_) => {}
+                Err(error) => log::warn!("Failed to fetch DeArrow branding: {}", error),
+            }
+        }));
+    }
+
+    fn update_title(&self) {
+        let imp = self.imp();
+        let Some(original_title) = imp.video.borrow().as_ref().map(|x| x.title.clone()) else {
+            return;
+        };
+        let branded_title = imp.branding.borrow().as_ref().and_then(|x| x.title.clone());
+        match branded_title {
+            Some(title) if !imp.original_title_button.is_active() => self.set_title(&title),
+            _ => self.set_title(&original_title),
+        }
+    }
+
+    fn fetch_votes(&self) {
+        let settings = self.model().settings();
+        // Invidious always reports zero dislikes, so only show them when they can be estimated
+        self.imp().dislikes_box.set_visible(false);
+        if !settings.boolean("dislikes-enabled") {
+            return;
+        }
+        let server = settings.string("dislikes-server").to_string();
+
+        MainContext::default().spawn_local(clone!(@weak self as obj => async move {
+            match votes(&server, &obj.video_id()).await {
+                Ok(votes) => {
+                    let imp = obj.imp();
+                    imp.dislikes_label
+                        .set_label(&format_number_magnitude(votes.dislikes));
+                    imp.rating_label
+                        .set_label(&format!("({:.0}% liked)", votes.ratio() * 100.0));
+                    imp.dislikes_box.set_visible(true);
+                }
+                Err(error) => log::warn!("Failed to fetch dislikes: {}", error),
+            }
+        }));
+    }
+
+    fn fetch_segments(&self) {
+        let model = self.model();
+        let settings = model.settings();
+        if !settings.boolean("sponsorblock-enabled") {
+            return;
+        }
+        let actions = model.segment_actions();
+        let categories: Vec<SegmentCategory> = actions
+            .iter()
+            .filter(|(_, action)| **action != SegmentAction::Ignore)
+            .map(|(category, _)| *category)
+            .collect();
+        let server = settings.string("sponsorblock-server").to_string();
+
+        MainContext::default().spawn_local(clone!(@weak self as obj => async move {
+            match skip_segments(&server, &obj.video_id(), &categories).await {
+                Ok(segments) => obj.set_segments(
+                    segments
+                        .into_iter()
+                        .filter_map(|segment| Some((*actions.get(&segment.category)?, segment)))
+                        .collect(),
+                ),
+                Err(error) => log::warn!("Failed to fetch SponsorBlock segments: {}", error),
+            }
+        }));
+    }
+
+    fn set_segments(&self, segments: Vec<(SegmentAction, Segment)>) {
+        let imp = self.imp();
+        let bar_segments: Vec<SeekBarSegment> = segments
+            .iter()
+            .filter_map(|(_, segment)| {
+                Some(SeekBarSegment {
+                    start: (segment.start * 1_000_000.0) as i64,
+                    end: (segment.end * 1_000_000.0) as i64,
+                    color: gdk::RGBA::parse(segment.category.color()).ok()?,
+                })
+            })
+            .collect();
+        imp.normal_seek_bar.set_segments(bar_segments.clone());
+        imp.fullscreen_seek_bar.set_segments(bar_segments);
+
+        imp.segments.replace(segments);
+        imp.skipped_segments.borrow_mut().clear();
+        self.update_segments();
+    }
+
+    fn update_segments(&self) {
+        let imp = self.imp();
+        let position = self.timestamp() as f64 / 1_000_000.0;
+        let current = imp
+            .segments
+            .borrow()
+            .iter()
+            .find(|(action, segment)| {
+                matches!(action, SegmentAction::Skip | SegmentAction::Button)
+                    && position >= segment.start
+                    && position < segment.end
+            })
+            .cloned();
+
+        let Some((action, segment)) = current else {
+            if self.can_skip() {
+                self.set_can_skip(false);
+            }
+            return;
+        };
+        let end = (segment.end * 1_000_000.0) as i64;
+        if action == SegmentAction::Skip
+            && imp
+                .skipped_segments
+                .borrow_mut()
+                .insert(segment.uuid.clone())
+        {
+            self.seek(end);
+            self.emit_by_name::<()>("toast", &[&format!("Skipped {}", segment.category.title())]);
+            return;
+        }
+        imp.skip_target.set(end);
+        let label = format!("Skip {}", segment.category.title());
+        if self.skip_label() != label {
+            self.set_skip_label(label);
+        }
+        if !self.can_skip() {
+            self.set_can_skip(true);
+        }
+    }
+
+    // Keys without modifiers are only handled here rather than as accelerators, so they
+    // reach text fields and focused widgets on this page first
+    fn create_key_controller(&self) {
+        let controller = gtk::EventControllerKey::new();
+        controller.set_propagation_phase(gtk::PropagationPhase::Bubble);
+        controller.connect_key_pressed(
+            clone!(@weak self as obj => @default-return glib::Propagation::Proceed, move |_, key, _, state| {
+                let modifiers = gdk::ModifierType::CONTROL_MASK
+                    | gdk::ModifierType::ALT_MASK
+                    | gdk::ModifierType::SUPER_MASK;
+                if state.intersects(modifiers) {
+                    return glib::Propagation::Proceed;
+                }
+                match player_shortcut(key) {
+                    Some((action, target)) => {
+                        if let Err(error) = obj.activate_action(action, target.as_ref()) {
+                            log::warn!("Failed to activate {}: {}", action, error);
+                        }
+                        glib::Propagation::Stop
+                    }
+                    None => glib::Propagation::Proceed,
+                }
+            }),
+        );
+        self.add_controller(controller);
+    }
+
     fn create_hover_controller(&self) {
         let imp = self.imp();
         let controller = gtk::EventControllerMotion::new();
@@ -228,13 +865,27 @@ impl VideoView {
             imp.headerbar_revealer.set_reveal_child(false);
         }));
         imp.hover_box.add_controller(controller);
+
+        // The seek bar sits on top of the video, so only show it while the pointer is moving
+        let controller = gtk::EventControllerMotion::new();
+        controller.connect_motion(clone!(@weak imp => move |_, _, _| {
+            imp.seek_revealer.set_reveal_child(true);
+            if let Some(source) = imp.seek_bar_timeout.take() {
+                source.remove();
+            }
+            let source = glib::timeout_add_local_once(SEEK_BAR_HIDE_DELAY, clone!(@weak imp => move || {
+                imp.seek_bar_timeout.take();
+                imp.seek_revealer.set_reveal_child(false);
+            }));
+            imp.seek_bar_timeout.replace(Some(source));
+        }));
+        imp.fullscreen_overlay.add_controller(controller);
     }
 
     fn set_video(&self, video: DetailedVideo) {
         let imp = self.imp();
 
         // Setup player
-        let _selected_stream = video.format_streams.last().unwrap();
         imp.fullscreen_stack
             .set_visible_child_name(if self.fullscreened() {
                 "fullscreened"
@@ -242,8 +893,14 @@ impl VideoView {
                 "normal"
             });
 
-        imp.video.set(video.clone()).unwrap();
+        imp.video.replace(None);
+        self.set_rate(self.model().channel_speed(&video.author_id));
+        imp.video.replace(Some(video.clone()));
+        self.model().record_history(&video);
         self.init_media_stream();
+        // Live streams can't be seeked through
+        imp.normal_seek_bar.set_visible(!video.live);
+        imp.fullscreen_seek_bar.set_visible(!video.live);
 
         // When using GtkVideo:autoplay and resizing the window, the video will play after being
         // paused. This will give the desired behavior that GtkVideo:autoplay does not
@@ -257,12 +914,15 @@ impl VideoView {
             .set_uri(video.author_thumbnails.last().unwrap().uri.clone());
         imp.likes_label
             .set_label(&format_number_magnitude(video.likes as u64));
-        imp.dislikes_label
-            .set_label(&format_number_magnitude(video.dislikes as u64));
         imp.views_label
             .set_label(&format_number_magnitude(video.views));
         imp.published_label.set_label(&video.published);
-        imp.description_label.set_label(&video.description);
+        imp.description_label
+            .set_markup(&html_to_pango(&video.description_html));
+        self.set_chapters(video.chapters());
+        self.fetch_segments();
+        self.fetch_votes();
+        self.fetch_branding();
         imp.recommended_grid
             .set_videos(video.recommended.as_slice());
         imp.recommended_grid.set_state(ResultPageState::Success);
@@ -275,20 +935,113 @@ impl VideoView {
             let instance = invidious.get_instance();
             let imp = obj.imp();
 
+            if let Some(source) = imp.countdown.take() {
+                source.remove();
+            }
             imp.instance_indicator.set_uri(instance.uri.clone());
             imp.result_page.set_state(ResultPageState::Loading);
 
             let result = instance.video(&video_id).await;
 
-            imp.result_page.set_state(
-                match result {
-                    Ok(video) => {
-                        obj.set_video(video);
-                        ResultPageState::Success
-                    },
-                    Err(error) => ResultPageState::Error(error.to_string())
+            match result {
+                Ok(video) if video.upcoming => {
+                    obj.set_title(&video.title);
+                    obj.show_premiere(video.premiere_timestamp.unwrap_or_default());
                 }
-            );
+                Ok(video) if video.stream_uri().is_none() => {
+                    imp.result_page.set_state(ResultPageState::Error(
+                        "This video has no playable streams".into(),
+                    ));
+                }
+                Ok(video) => {
+                    obj.fetch_storyboard(instance, &video);
+                    obj.set_video(video);
+                    imp.result_page.set_state(ResultPageState::Success);
+                }
+                Err(error) => imp.result_page.set_state(ResultPageState::Error(error.to_string())),
+            }
         }));
     }
+
+    fn show_premiere(&self, premiere: u64) {
+        let imp = self.imp();
+        if let Some(source) = imp.countdown.take() {
+            source.remove();
+        }
+        let source = if self.update_premiere_countdown(premiere) {
+            glib::timeout_add_seconds_local(
+                1,
+                clone!(@weak self as obj => @default-return glib::ControlFlow::Break, move || {
+                    if obj.update_premiere_countdown(premiere) {
+                        return glib::ControlFlow::Continue;
+                    }
+                    obj.imp().countdown.take();
+                    obj.fetch_video();
+                    glib::ControlFlow::Break
+                }),
+            )
+        } else {
+            // Premieres often start late, so keep checking until it goes live
+            glib::timeout_add_seconds_local_once(
+                PREMIERE_POLL_INTERVAL,
+                clone!(@weak self as obj => move || {
+                    obj.imp().countdown.take();
+                    obj.fetch_video();
+                }),
+            )
+        };
+        imp.countdown.replace(Some(source));
+    }
+
+    /// Returns false once the premiere should have started
+    fn update_premiere_countdown(&self, premiere: u64) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let remaining = premiere.saturating_sub(now);
+        let scheduled = glib::DateTime::from_unix_local(premiere as i64)
+            .and_then(|x| x.format("%c"))
+            .map(|x| format!("Scheduled for {}", x))
+            .unwrap_or_default();
+
+        let title = if remaining == 0 {
+            "Premiere Starting Soon".to_string()
+        } else if remaining >= 2 * 24 * 60 * 60 {
+            format!("Premieres in {} Days", remaining / (24 * 60 * 60))
+        } else {
+            format!("Premieres in {}", format_duration(remaining as u32))
+        };
+        self.imp().result_page.set_state(ResultPageState::Message((
+            "month-symbolic".into(),
+            title,
+            scheduled,
+        )));
+        remaining > 0
+    }
+}
+
+/// Action for a key pressed on the player, mostly the same keys as YouTube
+fn player_shortcut(key: gdk::Key) -> Option<(&'static str, Option<glib::Variant>)> {
+    if let Some(number) = key.to_unicode().and_then(|x| x.to_digit(10)) {
+        return Some(("win.seek-percent", Some((number as i32 * 10).to_variant())));
+    }
+    let shortcut = match key {
+        gdk::Key::f => ("win.toggle-fullscreen", None),
+        gdk::Key::space | gdk::Key::k => ("win.toggle-playback", None),
+        gdk::Key::j => ("win.seek-by", Some((-10i32).to_variant())),
+        gdk::Key::l => ("win.seek-by", Some(10i32.to_variant())),
+        gdk::Key::Left => ("win.seek-by", Some((-5i32).to_variant())),
+        gdk::Key::Right => ("win.seek-by", Some(5i32.to_variant())),
+        gdk::Key::comma => ("win.frame-step", Some((-1i32).to_variant())),
+        gdk::Key::period => ("win.frame-step", Some(1i32.to_variant())),
+        gdk::Key::Up => ("win.volume-up", None),
+        gdk::Key::Down => ("win.volume-down", None),
+        gdk::Key::m => ("win.toggle-mute", None),
+        gdk::Key::c => ("win.toggle-captions", None),
+        gdk::Key::greater => ("win.speed-up", None),
+        gdk::Key::less => ("win.slow-down", None),
+        _ => return None,
+    };
+    Some(shortcut)
 }
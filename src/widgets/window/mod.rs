@@ -1,16 +1,45 @@
 use adw::prelude::*;
 use adw::subclass::prelude::*;
-use glib::{clone, MainContext};
+use glib::{clone, closure_local, MainContext};
 use gtk::{gio, glib};
-use std::cell::OnceCell;
+use std::cell::{OnceCell, RefCell};
+use std::collections::HashSet;
 use std::sync::Arc;
 
+use crate::api::trending::TrendingCategory;
+use crate::api::{record_host, Mix, ResolvedUrl};
 use crate::appmodel::AppModel;
+use crate::markup::{parse_link, Link};
+use crate::player::PlayerStream;
+use crate::profiles::Profile;
+use crate::radio::Radio;
 use crate::widgets::{
-    channel_view::ChannelView, content_grid::ContentGrid, instance_indicator::InstanceIndicator,
+    channel_view::ChannelView, content_grid::ContentGrid, hashtag_view::HashtagView,
+    instance_indicator::InstanceIndicator, now_playing_bar::NowPlayingBar,
     playlist_view::PlaylistView, result_page::ResultPageState, video_view::VideoView,
 };
 
+const BACKGROUND_NOTIFICATION_ID: &str = "background-playback";
+// Actions that only make sense while a video is visible, they are disabled otherwise
+const PLAYER_ACTIONS: [&str; 14] = [
+    "toggle-playback",
+    "seek-by",
+    "seek-percent",
+    "frame-step",
+    "volume-up",
+    "volume-down",
+    "toggle-mute",
+    "toggle-captions",
+    "play-next",
+    "toggle-fullscreen",
+    "next-chapter",
+    "previous-chapter",
+    "speed-up",
+    "slow-down",
+];
+// Volume change for each press of the up and down keys
+const VOLUME_STEP: f64 = 0.05;
+
 mod imp {
 
     use super::*;
@@ -25,10 +54,34 @@ mod imp {
         #[template_child]
         pub popular_grid: TemplateChild<ContentGrid>,
         #[template_child]
-        pub trending_grid: TemplateChild<ContentGrid>,
+        pub trending_stack: TemplateChild<gtk::Stack>,
         #[template_child]
         pub search_grid: TemplateChild<ContentGrid>,
         #[template_child]
+        pub search_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub private_search_button: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub clear_search_history_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub search_history_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub search_history_empty: TemplateChild<adw::StatusPage>,
+        #[template_child]
+        pub subscriptions_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub subscriptions_empty: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub playlists_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub playlists_empty: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub clear_watch_history_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub watch_history_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub watch_history_empty: TemplateChild<gtk::Label>,
+        #[template_child]
         pub popular_instance_indicator: TemplateChild<InstanceIndicator>,
         #[template_child]
         pub trending_instance_indicator: TemplateChild<InstanceIndicator>,
@@ -42,8 +95,23 @@ mod imp {
         pub search_button: TemplateChild<gtk::ToggleButton>,
         #[template_child]
         pub title_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub now_playing_bar: TemplateChild<NowPlayingBar>,
+        #[template_child]
+        pub profile_button: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub profile_menu: TemplateChild<gio::Menu>,
 
         pub model: OnceCell<Arc<AppModel>>,
+        // Keeps the application running while audio plays with the window closed
+        pub hold_guard: RefCell<Option<gio::ApplicationHoldGuard>>,
+        // Trending categories are only loaded once they're looked at
+        pub trending_requested: RefCell<HashSet<TrendingCategory>>,
+        pub suggestions_popover: OnceCell<gtk::Popover>,
+        pub suggestions: RefCell<Vec<String>>,
+        // Suggestions for what was just searched for aren't needed anymore
+        pub last_search: RefCell<String>,
+        pub radio: RefCell<Option<Radio>>,
     }
 
     #[glib::object_subclass]
@@ -65,19 +133,70 @@ mod imp {
     impl ObjectImpl for PryvidWindow {
         fn constructed(&self) {
             self.parent_constructed();
-            self.obj().setup_gactions();
+            let obj = self.obj();
+            obj.setup_gactions();
+            self.navigation_view
+                .connect_visible_page_notify(clone!(@weak obj => move |_| {
+                    obj.update_player_actions();
+                }));
+            obj.update_player_actions();
+            self.now_playing_bar.connect_closure(
+                "ended",
+                false,
+                closure_local!(@watch obj => move |_: NowPlayingBar, video_id: String| {
+                    obj.advance_radio(&video_id);
+                }),
+            );
+            // History grows with every video, so the library is filled each time it's shown
+            self.view_stack
+                .connect_visible_child_name_notify(clone!(@weak obj => move |stack| {
+                    if stack.visible_child_name().as_deref() == Some("library") {
+                        obj.show_library();
+                    }
+                }));
+            obj.setup_search_suggestions();
             MainContext::default().spawn_local(clone!(@weak self as window => async move {
                 window.obj().build_search("").await;
             }));
         }
+
+        fn dispose(&self) {
+            if let Some(popover) = self.suggestions_popover.get() {
+                popover.unparent();
+            }
+        }
     }
     impl WidgetImpl for PryvidWindow {}
-    impl WindowImpl for PryvidWindow {}
+    impl WindowImpl for PryvidWindow {
+        fn close_request(&self) -> glib::Propagation {
+            if self.obj().hide_to_background() {
+                return glib::Propagation::Stop;
+            }
+            self.parent_close_request()
+        }
+    }
     impl ApplicationWindowImpl for PryvidWindow {}
     impl AdwApplicationWindowImpl for PryvidWindow {}
 
     #[gtk::template_callbacks]
     impl PryvidWindow {
+        #[template_callback]
+        fn on_search_entry_search_changed(&self, search_entry: gtk::SearchEntry) {
+            self.obj()
+                .update_suggestions(search_entry.text().to_string());
+        }
+        #[template_callback]
+        fn on_clear_search_history_clicked(&self, _: gtk::Button) {
+            let obj = self.obj();
+            obj.model().clear_search_history();
+            obj.show_search_history();
+        }
+        #[template_callback]
+        fn on_clear_watch_history_clicked(&self, _: gtk::Button) {
+            let obj = self.obj();
+            obj.model().clear_watch_history();
+            obj.show_library();
+        }
         #[template_callback]
         fn on_search_entry_activated(&self, search_entry: gtk::SearchEntry) {
             MainContext::default().spawn_local(
@@ -93,10 +212,16 @@ mod imp {
             }));
         }
         #[template_callback]
-        fn on_trending_grid_refresh(&self, _: ContentGrid) {
-            MainContext::default().spawn_local(clone!(@weak self as window => async move {
-                window.obj().build_trending().await;
-            }));
+        fn on_now_playing_bar_open(&self, bar: NowPlayingBar) {
+            let obj = self.obj();
+            if let Some(video_view) = bar.release() {
+                obj.expand_video_view(&video_view);
+            } else {
+                let video_id = bar.video_id();
+                let start = (bar.timestamp() / 1_000_000) as u32;
+                bar.stop();
+                obj.open_video(video_id, Some(start));
+            }
         }
         #[template_callback]
         fn on_search_button_toggled(&self, button: gtk::ToggleButton) {
@@ -104,7 +229,13 @@ mod imp {
                 self.title_stack.set_visible_child_name("search");
                 self.view_stack.set_visible_child_name("search");
                 self.search_entry.grab_focus();
+                if self.search_entry.text().is_empty() {
+                    self.obj().show_search_history();
+                }
             } else {
+                if let Some(popover) = self.suggestions_popover.get() {
+                    popover.popdown();
+                }
                 self.title_stack.set_visible_child_name("popular-trending");
                 self.view_stack.set_visible_child_name("popular");
             }
@@ -125,8 +256,22 @@ impl PryvidWindow {
             .build();
 
         // Setup window
-        window.imp().model.set(model).unwrap();
-        window.fetch_startup();
+        let imp = window.imp();
+        imp.popular_grid.set_model(model.clone());
+        imp.search_grid.set_model(model.clone());
+        imp.model.set(model).unwrap();
+        window.setup_trending();
+        window
+            .model()
+            .settings()
+            .bind(
+                "record-search-history",
+                &*window.imp().private_search_button,
+                "active",
+            )
+            .invert_boolean()
+            .build();
+        window.setup_profiles();
 
         window
     }
@@ -148,15 +293,7 @@ impl PryvidWindow {
             .parameter_type(Some(&String::static_variant_type()))
             .activate(move |win: &Self, _, param| {
                 if let Some(param) = param {
-                    let channel_id = param.get::<String>().unwrap();
-                    let nav_view = &win.imp().navigation_view;
-
-                    if nav_view.visible_page().unwrap().tag()
-                        != Some(glib::GString::from_string_unchecked(channel_id.clone()))
-                    {
-                        let channel_view = ChannelView::new(win.model(), channel_id);
-                        nav_view.push(&channel_view);
-                    }
+                    win.open_channel(param.get::<String>().unwrap());
                 }
             })
             .build();
@@ -165,15 +302,25 @@ impl PryvidWindow {
             .parameter_type(Some(&String::static_variant_type()))
             .activate(move |win: &Self, _, param| {
                 if let Some(param) = param {
-                    let playlist_id = param.get::<String>().unwrap();
-                    let nav_view = &win.imp().navigation_view;
-
-                    if nav_view.visible_page().unwrap().tag()
-                        != Some(glib::GString::from_string_unchecked(playlist_id.clone()))
-                    {
-                        let playlist_view = PlaylistView::new(win.model(), playlist_id);
-                        nav_view.push(&playlist_view);
-                    }
+                    win.open_playlist(param.get::<String>().unwrap());
+                }
+            })
+            .build();
+
+        let open_hashtag_action = gio::ActionEntry::builder("open-hashtag")
+            .parameter_type(Some(&String::static_variant_type()))
+            .activate(move |win: &Self, _, param| {
+                if let Some(param) = param {
+                    win.open_hashtag(param.get::<String>().unwrap());
+                }
+            })
+            .build();
+
+        let start_radio_action = gio::ActionEntry::builder("start-radio")
+            .parameter_type(Some(&String::static_variant_type()))
+            .activate(move |win: &Self, _, param| {
+                if let Some(param) = param {
+                    win.start_radio(param.get::<String>().unwrap());
                 }
             })
             .build();
@@ -182,23 +329,33 @@ impl PryvidWindow {
             .parameter_type(Some(&String::static_variant_type()))
             .activate(move |win: &Self, _, param| {
                 if let Some(param) = param {
-                    let video_id = param.get::<String>().unwrap();
-                    let nav_view = &win.imp().navigation_view;
-
-                    if nav_view.visible_page().unwrap().tag()
-                        != Some(glib::GString::from_string_unchecked(video_id.clone()))
-                    {
-                        let video_view = VideoView::new(win.model(), video_id);
-                        win.bind_property::<VideoView>(
-                            "fullscreened",
-                            video_view.as_ref(),
-                            "fullscreened",
-                        )
-                        .sync_create()
-                        .bidirectional()
-                        .build();
-                        nav_view.push(&video_view);
-                    }
+                    win.open_video(param.get::<String>().unwrap(), None);
+                }
+            })
+            .build();
+
+        let open_link_action = gio::ActionEntry::builder("open-link")
+            .parameter_type(Some(&String::static_variant_type()))
+            .activate(move |win: &Self, _, param| {
+                if let Some(param) = param {
+                    win.open_link(parse_link(&param.get::<String>().unwrap()));
+                }
+            })
+            .build();
+        let search_action = gio::ActionEntry::builder("search")
+            .parameter_type(Some(&String::static_variant_type()))
+            .activate(move |win: &Self, _, param| {
+                if let Some(param) = param {
+                    win.search(&param.get::<String>().unwrap());
+                }
+            })
+            .build();
+
+        let play_audio_action = gio::ActionEntry::builder("play-audio")
+            .parameter_type(Some(&<(String, i64)>::static_variant_type()))
+            .activate(move |win: &Self, _, param| {
+                if let Some((video_id, start)) = param.and_then(|x| x.get::<(String, i64)>()) {
+                    win.play_audio(video_id, start);
                 }
             })
             .build();
@@ -232,6 +389,197 @@ impl PryvidWindow {
             })
             .build();
 
+        let next_chapter_action = gio::ActionEntry::builder("next-chapter")
+            .parameter_type(None)
+            .activate(move |win: &Self, _, _| {
+                if let Some(video_view) = win.visible_video_view() {
+                    video_view.seek_chapter(true);
+                }
+            })
+            .build();
+        let previous_chapter_action = gio::ActionEntry::builder("previous-chapter")
+            .parameter_type(None)
+            .activate(move |win: &Self, _, _| {
+                if let Some(video_view) = win.visible_video_view() {
+                    video_view.seek_chapter(false);
+                }
+            })
+            .build();
+
+        let speed_up_action = gio::ActionEntry::builder("speed-up")
+            .parameter_type(None)
+            .activate(move |win: &Self, _, _| {
+                if let Some(video_view) = win.visible_video_view() {
+                    video_view.change_speed(true);
+                }
+            })
+            .build();
+        let slow_down_action = gio::ActionEntry::builder("slow-down")
+            .parameter_type(None)
+            .activate(move |win: &Self, _, _| {
+                if let Some(video_view) = win.visible_video_view() {
+                    video_view.change_speed(false);
+                }
+            })
+            .build();
+
+        let toggle_playback_action = gio::ActionEntry::builder("toggle-playback")
+            .activate(move |win: &Self, _, _| {
+                if let Some(video_view) = win.visible_video_view() {
+                    video_view.toggle_playing();
+                }
+            })
+            .build();
+        let seek_by_action = gio::ActionEntry::builder("seek-by")
+            .parameter_type(Some(&i32::static_variant_type()))
+            .activate(move |win: &Self, _, param| {
+                if let (Some(video_view), Some(seconds)) =
+                    (win.visible_video_view(), param.and_then(|x| x.get::<i32>()))
+                {
+                    video_view.seek_by(seconds.into());
+                }
+            })
+            .build();
+        let seek_percent_action = gio::ActionEntry::builder("seek-percent")
+            .parameter_type(Some(&i32::static_variant_type()))
+            .activate(move |win: &Self, _, param| {
+                if let (Some(video_view), Some(percent)) =
+                    (win.visible_video_view(), param.and_then(|x| x.get::<i32>()))
+                {
+                    video_view.seek_fraction(percent as f64 / 100.0);
+                }
+            })
+            .build();
+        let frame_step_action = gio::ActionEntry::builder("frame-step")
+            .parameter_type(Some(&i32::static_variant_type()))
+            .activate(move |win: &Self, _, param| {
+                if let (Some(video_view), Some(direction)) =
+                    (win.visible_video_view(), param.and_then(|x| x.get::<i32>()))
+                {
+                    video_view.step_frame(direction > 0);
+                }
+            })
+            .build();
+        let volume_up_action = gio::ActionEntry::builder("volume-up")
+            .activate(move |win: &Self, _, _| {
+                if let Some(video_view) = win.visible_video_view() {
+                    video_view.change_volume(VOLUME_STEP);
+                }
+            })
+            .build();
+        let volume_down_action = gio::ActionEntry::builder("volume-down")
+            .activate(move |win: &Self, _, _| {
+                if let Some(video_view) = win.visible_video_view() {
+                    video_view.change_volume(-VOLUME_STEP);
+                }
+            })
+            .build();
+        let toggle_mute_action = gio::ActionEntry::builder("toggle-mute")
+            .activate(move |win: &Self, _, _| {
+                if let Some(video_view) = win.visible_video_view() {
+                    video_view.toggle_muted();
+                }
+            })
+            .build();
+        let toggle_captions_action = gio::ActionEntry::builder("toggle-captions")
+            .activate(move |win: &Self, _, _| {
+                if let Some(video_view) = win.visible_video_view() {
+                    let message = match video_view.toggle_captions() {
+                        Some(label) => format!("Captions: {}", label),
+                        None => "Captions off".to_string(),
+                    };
+                    win.imp()
+                        .toast_overlay
+                        .add_toast(adw::Toast::builder().title(message).timeout(2).build());
+                }
+            })
+            .build();
+        let play_next_action = gio::ActionEntry::builder("play-next")
+            .activate(move |win: &Self, _, _| {
+                if let Some(video_view) = win.visible_video_view() {
+                    if !win.advance_radio(&video_view.video_id()) {
+                        video_view.play_next();
+                    }
+                }
+            })
+            .build();
+
+        let block_channel_action = gio::ActionEntry::builder("block-channel")
+            .parameter_type(Some(&<(String, String)>::static_variant_type()))
+            .activate(move |win: &Self, _, param| {
+                if let Some((id, name)) = param.and_then(|x| x.get::<(String, String)>()) {
+                    win.block_channel(&id, &name);
+                }
+            })
+            .build();
+        let unblock_channel_action = gio::ActionEntry::builder("unblock-channel")
+            .parameter_type(Some(&String::static_variant_type()))
+            .activate(move |win: &Self, _, param| {
+                if let Some(id) = param.and_then(|x| x.get::<String>()) {
+                    let model = win.model();
+                    let mut filter = model.content_filter();
+                    filter.unblock_channel(&id);
+                    model.set_content_filter(&filter);
+                }
+            })
+            .build();
+
+        let new_playlist_action = gio::ActionEntry::builder("new-playlist")
+            .activate(move |win: &Self, _, _| win.show_new_playlist_dialog())
+            .build();
+        let add_to_playlist_action = gio::ActionEntry::builder("add-to-playlist")
+            .parameter_type(Some(&String::static_variant_type()))
+            .activate(move |win: &Self, _, param| {
+                if let Some(id) = param.and_then(|x| x.get::<String>()) {
+                    win.add_to_playlist(&id);
+                }
+            })
+            .build();
+        let remove_playlist_action = gio::ActionEntry::builder("remove-playlist")
+            .parameter_type(Some(&String::static_variant_type()))
+            .activate(move |win: &Self, _, param| {
+                if let Some(id) = param.and_then(|x| x.get::<String>()) {
+                    win.confirm_remove_playlist(&id);
+                }
+            })
+            .build();
+        let remove_from_playlist_action = gio::ActionEntry::builder("remove-from-playlist")
+            .parameter_type(Some(&<(String, String)>::static_variant_type()))
+            .activate(move |win: &Self, _, param| {
+                if let Some((id, video_id)) = param.and_then(|x| x.get::<(String, String)>()) {
+                    win.model().remove_from_playlist(&id, &video_id);
+                    win.show_library();
+                }
+            })
+            .build();
+
+        let switch_profile_action = gio::ActionEntry::builder("switch-profile")
+            .parameter_type(Some(&String::static_variant_type()))
+            .state("".to_variant())
+            .activate(move |win: &Self, _, param| {
+                if let Some(id) = param.and_then(|x| x.get::<String>()) {
+                    win.request_profile(&id);
+                }
+            })
+            .build();
+        let add_profile_action = gio::ActionEntry::builder("add-profile")
+            .activate(move |win: &Self, _, _| win.show_add_profile_dialog())
+            .build();
+
+        let start_search_action = gio::ActionEntry::builder("start-search")
+            .activate(move |win: &Self, _, _| {
+                let imp = win.imp();
+                imp.navigation_view.pop_to_tag("home");
+                imp.search_button.set_active(true);
+                imp.search_entry.grab_focus();
+            })
+            .build();
+        let go_back_action = gio::ActionEntry::builder("go-back")
+            .activate(move |win: &Self, _, _| {
+                win.imp().navigation_view.pop();
+            })
+            .build();
+
         // Not sure if I'm happy with this or not
         // As of September 1st, it serves its purpose
         let escape_pressed_action = gio::ActionEntry::builder("escape-pressed")
@@ -252,31 +600,915 @@ impl PryvidWindow {
             open_channel_action,
             open_video_action,
             open_playlist_action,
+            open_hashtag_action,
+            start_radio_action,
+            open_link_action,
+            search_action,
+            play_audio_action,
             fullscreen_action,
             unfullscreen_action,
             toggle_fullscreen_action,
+            next_chapter_action,
+            previous_chapter_action,
+            speed_up_action,
+            slow_down_action,
+            toggle_playback_action,
+            seek_by_action,
+            seek_percent_action,
+            frame_step_action,
+            volume_up_action,
+            volume_down_action,
+            toggle_mute_action,
+            toggle_captions_action,
+            play_next_action,
+            block_channel_action,
+            unblock_channel_action,
+            new_playlist_action,
+            add_to_playlist_action,
+            remove_playlist_action,
+            remove_from_playlist_action,
+            switch_profile_action,
+            add_profile_action,
+            start_search_action,
+            go_back_action,
             escape_pressed_action,
         ]);
     }
 
+    fn block_channel(&self, id: &str, name: &str) {
+        let model = self.model();
+        let mut filter = model.content_filter();
+        filter.block_channel(id, name);
+        model.set_content_filter(&filter);
+
+        let toast = adw::Toast::builder()
+            .title(format!("Blocked {}", glib::markup_escape_text(name)))
+            .button_label("Undo")
+            .action_name("win.unblock-channel")
+            .action_target(&id.to_variant())
+            .build();
+        self.imp().toast_overlay.add_toast(toast);
+    }
+
+    /// Asks for the name of a new playlist. The video that is open, if any, is saved to it
+    fn show_new_playlist_dialog(&self) {
+        let entry = gtk::Entry::builder()
+            .placeholder_text("Name")
+            .activates_default(true)
+            .build();
+        let dialog = adw::MessageDialog::builder()
+            .heading("New Playlist")
+            .body("Playlists are only kept in the current profile")
+            .extra_child(&entry)
+            .modal(true)
+            .transient_for(self)
+            .build();
+        dialog.add_responses(&[("cancel", "Cancel"), ("create", "Create")]);
+        dialog.set_response_appearance("create", adw::ResponseAppearance::Suggested);
+        dialog.set_response_enabled("create", false);
+        dialog.set_default_response(Some("create"));
+        dialog.set_close_response("cancel");
+        entry.connect_changed(clone!(@weak dialog => move |entry| {
+            dialog.set_response_enabled("create", !entry.text().trim().is_empty());
+        }));
+        dialog.connect_response(
+            Some("create"),
+            clone!(@weak self as win, @weak entry => move |_, _| {
+                let id = win.model().create_playlist(entry.text().trim());
+                if win.visible_video_view().is_some() {
+                    win.add_to_playlist(&id);
+                }
+                win.show_library();
+            }),
+        );
+        dialog.present();
+    }
+
+    /// Saves the video that is open to a playlist of the active profile
+    fn add_to_playlist(&self, id: &str) {
+        let Some(video) = self.visible_video_view().and_then(|x| x.video()) else {
+            return;
+        };
+        let model = self.model();
+        let Some(playlist) = model.playlists().into_iter().find(|x| x.id == id) else {
+            return;
+        };
+        let message = if playlist.contains(&video.id) {
+            format!("Already in {}", glib::markup_escape_text(&playlist.name))
+        } else {
+            model.add_to_playlist(id, &video);
+            format!("Saved to {}", glib::markup_escape_text(&playlist.name))
+        };
+        self.imp()
+            .toast_overlay
+            .add_toast(adw::Toast::builder().title(message).timeout(2).build());
+    }
+
+    fn confirm_remove_playlist(&self, id: &str) {
+        let Some(playlist) = self.model().playlists().into_iter().find(|x| x.id == id) else {
+            return;
+        };
+        let dialog = adw::MessageDialog::builder()
+            .heading(format!("Remove {}?", playlist.name))
+            .body("The videos in it won't be saved anywhere else")
+            .modal(true)
+            .transient_for(self)
+            .build();
+        dialog.add_responses(&[("cancel", "Cancel"), ("remove", "Remove")]);
+        dialog.set_response_appearance("remove", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+        let id = id.to_string();
+        dialog.connect_response(
+            Some("remove"),
+            clone!(@weak self as win => move |_, _| {
+                win.model().remove_playlist(&id);
+                win.show_library();
+            }),
+        );
+        dialog.present();
+    }
+
+    fn setup_profiles(&self) {
+        let imp = self.imp();
+        let model = self.model();
+        // Profiles can be renamed or removed in preferences, so the menu is refreshed every
+        // time it opens
+        imp.profile_button
+            .connect_active_notify(clone!(@weak self as win => move |button| {
+                if button.is_active() {
+                    win.update_profile_menu();
+                }
+            }));
+        model.settings().connect_changed(
+            Some("active-profile"),
+            clone!(@weak self as win => move |_, _| win.on_profile_changed()),
+        );
+        self.update_profile_menu();
+
+        let profile = model.active_profile();
+        if profile.is_locked() {
+            // Waits for the window to be shown, so the dialog has something to be on top of
+            glib::idle_add_local_once(clone!(@weak self as win => move || {
+                win.unlock_profile(profile, true);
+            }));
+        } else {
+            self.fetch_startup();
+        }
+    }
+
+    fn update_profile_menu(&self) {
+        let imp = self.imp();
+        let model = self.model();
+        let active = model.active_profile();
+
+        let profiles = gio::Menu::new();
+        for profile in model.profiles() {
+            let item = gio::MenuItem::new(Some(&profile.name), None);
+            item.set_action_and_target_value(
+                Some("win.switch-profile"),
+                Some(&profile.id.to_variant()),
+            );
+            profiles.append_item(&item);
+        }
+        imp.profile_menu.remove_all();
+        imp.profile_menu.append_section(None, &profiles);
+        imp.profile_menu
+            .append(Some("Add Profile…"), Some("win.add-profile"));
+
+        imp.profile_button
+            .set_tooltip_text(Some(&format!("Profile: {}", active.name)));
+        if let Some(action) = self
+            .lookup_action("switch-profile")
+            .and_downcast::<gio::SimpleAction>()
+        {
+            action.set_state(&active.id.to_variant());
+        }
+    }
+
+    /// Switches to another profile, asking for its PIN first if it has one
+    fn request_profile(&self, id: &str) {
+        let model = self.model();
+        if id == model.active_profile_id() {
+            return;
+        }
+        let Some(profile) = model.profiles().into_iter().find(|x| x.id == id) else {
+            return;
+        };
+        if profile.is_locked() {
+            self.unlock_profile(profile, false);
+        } else {
+            model.switch_profile(&profile.id);
+        }
+    }
+
+    /// Switches to a profile that has to be unlocked first. If it is already active, as no
+    /// unlocked profile was left to use, cancelling closes the window like at startup
+    pub fn ask_for_profile(&self, id: &str) {
+        let model = self.model();
+        let Some(profile) = model.profiles().into_iter().find(|x| x.id == id) else {
+            return;
+        };
+        if id == model.active_profile_id() {
+            self.unlock_profile(profile, true);
+        } else {
+            self.request_profile(id);
+        }
+    }
+
+    /// Asks for the PIN of a locked profile. At startup the profile is already active, so
+    /// cancelling falls back to an unlocked one
+    fn unlock_profile(&self, profile: Profile, startup: bool) {
+        let entry = gtk::PasswordEntry::builder()
+            .show_peek_icon(true)
+            .activates_default(true)
+            .build();
+        let dialog = adw::MessageDialog::builder()
+            .heading(format!("Unlock {}", profile.name))
+            .body("Enter the PIN of this profile")
+            .extra_child(&entry)
+            .modal(true)
+            .transient_for(self)
+            .build();
+        dialog.add_responses(&[("cancel", "Cancel"), ("unlock", "Unlock")]);
+        dialog.set_response_appearance("unlock", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("unlock"));
+        dialog.set_close_response("cancel");
+        dialog.connect_response(
+            None,
+            clone!(@weak self as win, @weak entry => move |_, response| {
+                let model = win.model();
+                if response == "unlock" {
+                    if !profile.unlocks_with(&entry.text()) {
+                        win.imp()
+                            .toast_overlay
+                            .add_toast(adw::Toast::builder().title("Wrong PIN").build());
+                        win.unlock_profile(profile.clone(), startup);
+                    } else if startup {
+                        win.fetch_startup();
+                    } else {
+                        model.switch_profile(&profile.id);
+                    }
+                } else if startup {
+                    match model.profiles().into_iter().find(|x| !x.is_locked()) {
+                        Some(fallback) => model.switch_profile(&fallback.id),
+                        None => win.close(),
+                    }
+                }
+            }),
+        );
+        dialog.present();
+    }
+
+    fn show_add_profile_dialog(&self) {
+        let entry = gtk::Entry::builder()
+            .placeholder_text("Name")
+            .activates_default(true)
+            .build();
+        let dialog = adw::MessageDialog::builder()
+            .heading("Add Profile")
+            .body(
+                "Each profile has its own subscriptions, history, playlists, filters and instance",
+            )
+            .extra_child(&entry)
+            .modal(true)
+            .transient_for(self)
+            .build();
+        dialog.add_responses(&[("cancel", "Cancel"), ("add", "Add")]);
+        dialog.set_response_appearance("add", adw::ResponseAppearance::Suggested);
+        dialog.set_response_enabled("add", false);
+        dialog.set_default_response(Some("add"));
+        dialog.set_close_response("cancel");
+        entry.connect_changed(clone!(@weak dialog => move |entry| {
+            dialog.set_response_enabled("add", !entry.text().trim().is_empty());
+        }));
+        dialog.connect_response(
+            Some("add"),
+            clone!(@weak self as win, @weak entry => move |_, _| {
+                let model = win.model();
+                let id = model.add_profile(entry.text().trim());
+                model.switch_profile(&id);
+            }),
+        );
+        dialog.present();
+    }
+
+    /// Starts over from the home page with the content of the new profile
+    fn on_profile_changed(&self) {
+        let imp = self.imp();
+        // Nothing from the previous profile should keep playing
+        if let Some(video_view) = self.visible_video_view() {
+            video_view.set_playing(false);
+        }
+        imp.now_playing_bar.stop();
+        imp.navigation_view.pop_to_tag("home");
+        imp.search_button.set_active(false);
+        imp.search_entry.set_text("");
+        self.update_profile_menu();
+        self.fetch_startup();
+        if imp.view_stack.visible_child_name().as_deref() == Some("library") {
+            self.show_library();
+        }
+
+        let name = self.model().active_profile().name;
+        imp.toast_overlay.add_toast(
+            adw::Toast::builder()
+                .title(format!("Switched to {}", glib::markup_escape_text(&name)))
+                .build(),
+        );
+    }
+
+    fn update_player_actions(&self) {
+        let enabled = self.visible_video_view().is_some();
+        for name in PLAYER_ACTIONS {
+            if let Some(action) = self.lookup_action(name).and_downcast::<gio::SimpleAction>() {
+                action.set_enabled(enabled);
+            }
+        }
+    }
+
+    fn open_channel(&self, channel_id: String) {
+        let nav_view = &self.imp().navigation_view;
+
+        if nav_view.visible_page().unwrap().tag()
+            != Some(glib::GString::from_string_unchecked(channel_id.clone()))
+        {
+            let channel_view = ChannelView::new(self.model(), channel_id);
+            nav_view.push(&channel_view);
+        }
+    }
+
+    fn open_playlist(&self, playlist_id: String) {
+        let nav_view = &self.imp().navigation_view;
+
+        if nav_view.visible_page().unwrap().tag()
+            != Some(glib::GString::from_string_unchecked(playlist_id.clone()))
+        {
+            let playlist_view = PlaylistView::new(self.model(), playlist_id);
+            nav_view.push(&playlist_view);
+        }
+    }
+
+    fn open_hashtag(&self, hashtag: String) {
+        let nav_view = &self.imp().navigation_view;
+        let hashtag = hashtag.trim_start_matches('#').to_string();
+
+        if nav_view.visible_page().unwrap().tag()
+            != Some(glib::GString::from_string_unchecked(hashtag.clone()))
+        {
+            let hashtag_view = HashtagView::new(self.model(), hashtag);
+            nav_view.push(&hashtag_view);
+        }
+    }
+
+    fn open_video(&self, video_id: String, start: Option<u32>) {
+        let imp = self.imp();
+        let nav_view = &imp.navigation_view;
+
+        // Picking something else to watch ends the radio
+        let outside_radio = imp
+            .radio
+            .borrow()
+            .as_ref()
+            .is_some_and(|x| x.current() != video_id);
+        if outside_radio {
+            imp.radio.take();
+        }
+
+        // The mini player already has this video loaded
+        if let Some(video_view) = imp
+            .now_playing_bar
+            .page()
+            .filter(|x| x.video_id() == video_id)
+        {
+            imp.now_playing_bar.release();
+            self.expand_video_view(&video_view);
+            if let Some(start) = start {
+                video_view.seek(start as i64 * 1_000_000);
+            }
+            return;
+        }
+
+        if nav_view.visible_page().unwrap().tag()
+            != Some(glib::GString::from_string_unchecked(video_id.clone()))
+        {
+            imp.now_playing_bar.stop();
+            // Only one video plays at a time, the one being left shouldn't be minimized
+            if let Some(video_view) = self.visible_video_view() {
+                video_view.set_playing(false);
+            }
+            let video_view = VideoView::new(self.model(), video_id);
+            if let Some(start) = start {
+                video_view.set_timestamp(start as i64 * 1_000_000);
+            }
+            self.bind_property::<VideoView>("fullscreened", video_view.as_ref(), "fullscreened")
+                .sync_create()
+                .bidirectional()
+                .build();
+            video_view.connect_closure(
+                "toast",
+                false,
+                closure_local!(@watch self as win => move |_: VideoView, message: String| {
+                    win.imp()
+                        .toast_overlay
+                        .add_toast(adw::Toast::builder().title(message).timeout(2).build());
+                }),
+            );
+            video_view.connect_closure(
+                "ended",
+                false,
+                closure_local!(@watch self as win => move |video_view: VideoView| {
+                    win.advance_radio(&video_view.video_id());
+                }),
+            );
+            video_view.connect_closure(
+                "minimize",
+                false,
+                closure_local!(@watch self as win => move |video_view: VideoView| {
+                    win.imp().now_playing_bar.minimize(&video_view);
+                }),
+            );
+            // Going back to a minimized video takes it out of the mini player
+            video_view.connect_showing(clone!(@weak self as win => move |video_view| {
+                let bar = &win.imp().now_playing_bar;
+                if bar.page().as_ref() == Some(video_view) {
+                    bar.release();
+                }
+            }));
+            nav_view.push(&video_view);
+        } else if let (Some(start), Some(video_view)) = (start, self.visible_video_view()) {
+            video_view.seek(start as i64 * 1_000_000);
+        }
+    }
+
+    /// Plays `video_id` and keeps going with videos from its mix once it ends
+    fn start_radio(&self, video_id: String) {
+        self.imp().radio.replace(Some(Radio::new(&video_id)));
+        self.fill_radio(&video_id);
+        self.open_video(video_id, None);
+        self.imp().toast_overlay.add_toast(
+            adw::Toast::builder()
+                .title("Radio started")
+                .timeout(2)
+                .build(),
+        );
+    }
+
+    /// Queues the videos of the mix around `video_id`
+    fn fill_radio(&self, video_id: &str) {
+        let mix_id = Mix::id_for_video(video_id);
+        MainContext::default().spawn_local(clone!(@weak self as win => async move {
+            let instance = win.model().invidious().get_instance();
+            match instance.mix(&mix_id).await {
+                Ok(mix) => {
+                    if let Some(radio) = win.imp().radio.borrow_mut().as_mut() {
+                        radio.extend(mix);
+                    }
+                }
+                Err(error) => log::warn!("Failed to fetch mix {}: {}", mix_id, error),
+            }
+        }));
+    }
+
+    /// Opens the next video of the radio if `video_id` is the one it's playing. Returns
+    /// whether it did
+    fn advance_radio(&self, video_id: &str) -> bool {
+        let (next, needs_more) = {
+            let mut radio = self.imp().radio.borrow_mut();
+            match radio.as_mut() {
+                Some(radio) if radio.current() == video_id => (radio.advance(), radio.needs_more()),
+                _ => return false,
+            }
+        };
+        // Mixes are only about 50 videos long, the radio goes on with the mix of the
+        // video it has got to
+        if needs_more {
+            self.fill_radio(next.as_ref().map(|x| x.id.as_str()).unwrap_or(video_id));
+        }
+        let Some(video) = next else {
+            return false;
+        };
+        // A video that ended in the mini player is followed there, rather than by a page
+        // that takes over the window
+        let bar = &self.imp().now_playing_bar;
+        if bar.active() && bar.video_id() == video_id {
+            self.play_in_bar(video.id, 0, bar.page().is_none());
+        } else {
+            self.open_video(video.id, None);
+        }
+        true
+    }
+
+    fn open_link(&self, link: Link) {
+        match link {
+            Link::Video { id, start } => self.open_video(id, start),
+            Link::Channel(id) => self.open_channel(id),
+            Link::Playlist(id) => self.open_playlist(id),
+            Link::Hashtag(tag) => self.open_hashtag(tag),
+            Link::Search(query) => self.search(&query),
+            Link::Resolve(uri) => {
+                MainContext::default().spawn_local(clone!(@weak self as win => async move {
+                    let instance = win.model().invidious().get_instance();
+                    match instance.resolve_url(&uri).await {
+                        Ok(ResolvedUrl { video_id: Some(id), start_time_seconds, .. }) => {
+                            win.open_video(id, start_time_seconds)
+                        }
+                        Ok(ResolvedUrl { playlist_id: Some(id), .. }) => win.open_playlist(id),
+                        Ok(ResolvedUrl { ucid: Some(id), .. }) => win.open_channel(id),
+                        _ => win.confirm_external_link(uri),
+                    }
+                }));
+            }
+            Link::External(uri) => self.confirm_external_link(uri),
+        }
+    }
+
+    /// Shows a video page again, wherever it is in the navigation stack
+    fn expand_video_view(&self, video_view: &VideoView) {
+        let nav_view = &self.imp().navigation_view;
+        let in_stack = nav_view
+            .navigation_stack()
+            .iter::<adw::NavigationPage>()
+            .flatten()
+            .any(|page| &page == video_view.upcast_ref::<adw::NavigationPage>());
+        if in_stack {
+            nav_view.pop_to_page(video_view);
+        } else {
+            nav_view.push(video_view);
+        }
+    }
+
+    /// Plays only the audio of a video in the now playing bar, `start` is in microseconds
+    fn play_audio(&self, video_id: String, start: i64) {
+        // Listening replaces the video page it was started from
+        if let Some(video_view) = self.visible_video_view() {
+            video_view.set_playing(false);
+            self.imp().navigation_view.pop();
+        }
+        self.play_in_bar(video_id, start, true);
+    }
+
+    /// Plays a video in the now playing bar without opening its page, `start` is in
+    /// microseconds
+    fn play_in_bar(&self, video_id: String, start: i64, audio_only: bool) {
+        MainContext::default().spawn_local(clone!(@weak self as win => async move {
+            let instance = win.model().invidious().get_instance();
+            let message = match instance.video(&video_id).await {
+                Ok(video) => {
+                    let uri = if audio_only {
+                        video.audio_uri()
+                    } else {
+                        video.stream_uri()
+                    };
+                    match uri {
+                        Some(uri) => {
+                            record_host(uri);
+                            let model = win.model();
+                            let stream = PlayerStream::for_uri(uri);
+                            stream.set_rate(model.channel_speed(&video.author_id));
+                            model.settings().bind("volume", &stream, "volume").build();
+                            model.settings().bind("muted", &stream, "muted").build();
+                            win.imp().now_playing_bar.play(stream.upcast_ref(), &video, start);
+                            return;
+                        }
+                        None => "This video has nothing to play".to_string(),
+                    }
+                }
+                Err(error) => format!("Failed to load the video: {}", error),
+            };
+            win.imp()
+                .toast_overlay
+                .add_toast(adw::Toast::builder().title(message).build());
+        }));
+    }
+
+    /// Hides the window instead of closing it while audio is playing, returns whether it did
+    fn hide_to_background(&self) -> bool {
+        let imp = self.imp();
+        let Some(app) = self.application() else {
+            return false;
+        };
+        if !imp.now_playing_bar.playing() || !self.model().settings().boolean("background-playback")
+        {
+            return false;
+        }
+
+        imp.hold_guard.replace(Some(app.hold()));
+        self.set_visible(false);
+
+        let notification = gio::Notification::new("Playing in the Background");
+        notification.set_body(Some(&imp.now_playing_bar.title()));
+        notification.set_default_action("app.show-window");
+        notification.add_button("Stop", "app.stop-playback");
+        app.send_notification(Some(BACKGROUND_NOTIFICATION_ID), &notification);
+        true
+    }
+
+    pub fn show_from_background(&self) {
+        if let Some(app) = self.application() {
+            app.withdraw_notification(BACKGROUND_NOTIFICATION_ID);
+        }
+        self.imp().hold_guard.take();
+        self.present();
+    }
+
+    pub fn stop_background_playback(&self) {
+        let imp = self.imp();
+        if let Some(app) = self.application() {
+            app.withdraw_notification(BACKGROUND_NOTIFICATION_ID);
+        }
+        imp.now_playing_bar.stop();
+        // Only close the window if it was hidden, otherwise just stop playing
+        if imp.hold_guard.take().is_some() {
+            self.close();
+        }
+    }
+
+    fn confirm_external_link(&self, uri: String) {
+        let dialog = adw::MessageDialog::builder()
+            .heading("Open External Link?")
+            .body(format!(
+                "This link leads outside of Pryvid and will open in your browser:\n\n{}",
+                uri
+            ))
+            .modal(true)
+            .transient_for(self)
+            .build();
+        dialog.add_responses(&[("cancel", "Cancel"), ("open", "Open Link")]);
+        dialog.set_response_appearance("open", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+        dialog.connect_response(
+            Some("open"),
+            clone!(@weak self as win => move |_, _| {
+                gtk::UriLauncher::new(&uri).launch(
+                    Some(&win),
+                    gio::Cancellable::NONE,
+                    clone!(@weak win => move |result| {
+                        if let Err(error) = result {
+                            win.imp().toast_overlay.add_toast(
+                                adw::Toast::builder()
+                                    .title(format!("Failed to open link: {}", error))
+                                    .build(),
+                            );
+                        }
+                    }),
+                );
+            }),
+        );
+        dialog.present();
+    }
+
+    fn search(&self, query: &str) {
+        let imp = self.imp();
+        imp.navigation_view.pop_to_tag("home");
+        imp.search_button.set_active(true);
+        imp.search_entry.set_text(query);
+        MainContext::default().spawn_local(
+            clone!(@weak self as win, @to_owned query => async move {
+                win.build_search(&query).await;
+            }),
+        );
+    }
+
+    fn visible_video_view(&self) -> Option<VideoView> {
+        self.imp()
+            .navigation_view
+            .visible_page()
+            .and_then(|page| page.downcast::<VideoView>().ok())
+    }
+
     fn model(&self) -> Arc<AppModel> {
         self.imp().model.get().unwrap().clone()
     }
 
+    fn setup_search_suggestions(&self) {
+        let imp = self.imp();
+        let list = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .build();
+        list.connect_row_activated(clone!(@weak self as win => move |_, row| {
+            let suggestion = win
+                .imp()
+                .suggestions
+                .borrow()
+                .get(row.index() as usize)
+                .cloned();
+            if let Some(suggestion) = suggestion {
+                win.search(&suggestion);
+            }
+        }));
+        // Doesn't take focus away from the entry, so typing can go on
+        let popover = gtk::Popover::builder()
+            .child(&list)
+            .autohide(false)
+            .has_arrow(false)
+            .position(gtk::PositionType::Bottom)
+            .halign(gtk::Align::Start)
+            .build();
+        popover.add_css_class("menu");
+        popover.set_parent(&*imp.search_entry);
+        imp.suggestions_popover.set(popover).unwrap();
+
+        imp.search_history_list
+            .connect_row_activated(clone!(@weak self as win => move |_, row| {
+                if let Some(row) = row.downcast_ref::<adw::ActionRow>() {
+                    win.search(&row.title());
+                }
+            }));
+    }
+
+    /// Suggests searches for what's being typed. Typing is debounced by the entry
+    fn update_suggestions(&self, query: String) {
+        let imp = self.imp();
+        let popover = imp.suggestions_popover.get().unwrap();
+        if query.trim().is_empty() || query == *imp.last_search.borrow() {
+            popover.popdown();
+            return;
+        }
+
+        MainContext::default().spawn_local(clone!(@weak self as win => async move {
+            let imp = win.imp();
+            let instance = win.model().invidious().get_instance();
+            let suggestions = match instance.search_suggestions(&query).await {
+                Ok(suggestions) => suggestions,
+                Err(error) => {
+                    log::warn!("Failed to get search suggestions: {}", error);
+                    Vec::new()
+                }
+            };
+            // Typing went on or the search was made in the meantime
+            if imp.search_entry.text() != query || query == *imp.last_search.borrow() {
+                return;
+            }
+
+            let popover = imp.suggestions_popover.get().unwrap();
+            if suggestions.is_empty() {
+                popover.popdown();
+                return;
+            }
+            let list = popover.child().and_downcast::<gtk::ListBox>().unwrap();
+            while let Some(row) = list.row_at_index(0) {
+                list.remove(&row);
+            }
+            for suggestion in &suggestions {
+                let label = gtk::Label::builder()
+                    .label(suggestion)
+                    .xalign(0.0)
+                    .build();
+                list.append(&label);
+            }
+            imp.suggestions.replace(suggestions);
+            popover.popup();
+        }));
+    }
+
+    /// Shown instead of results while nothing is searched for
+    fn show_search_history(&self) {
+        let imp = self.imp();
+        let list = &imp.search_history_list;
+        while let Some(row) = list.row_at_index(0) {
+            list.remove(&row);
+        }
+
+        let history = self.model().search_history();
+        for query in &history {
+            let row = adw::ActionRow::builder()
+                .title(query)
+                .use_markup(false)
+                .activatable(true)
+                .build();
+            row.add_prefix(&gtk::Image::from_icon_name("document-open-recent-symbolic"));
+            let button = gtk::Button::builder()
+                .icon_name("edit-delete-symbolic")
+                .tooltip_text("Remove From History")
+                .valign(gtk::Align::Center)
+                .build();
+            button.add_css_class("flat");
+            button.connect_clicked(clone!(@weak self as win, @to_owned query => move |_| {
+                win.model().remove_search(&query);
+                win.show_search_history();
+            }));
+            row.add_suffix(&button);
+            list.append(&row);
+        }
+        list.set_visible(!history.is_empty());
+        imp.clear_search_history_button
+            .set_sensitive(!history.is_empty());
+        imp.search_history_empty.set_visible(history.is_empty());
+        imp.search_stack.set_visible_child_name("history");
+    }
+
+    /// Fills the library with the active profile's subscriptions, playlists and watch history
+    fn show_library(&self) {
+        let imp = self.imp();
+        let model = self.model();
+
+        let list = &imp.subscriptions_list;
+        while let Some(row) = list.row_at_index(0) {
+            list.remove(&row);
+        }
+        let subscriptions = model.subscriptions();
+        for subscription in &subscriptions {
+            let row = adw::ActionRow::builder()
+                .title(&subscription.name)
+                .use_markup(false)
+                .activatable(true)
+                .build();
+            row.add_prefix(&gtk::Image::from_icon_name("avatar-default-symbolic"));
+            row.add_suffix(&gtk::Image::from_icon_name("go-next-symbolic"));
+            row.set_action_name(Some("win.open-channel"));
+            row.set_action_target_value(Some(&subscription.id.to_variant()));
+            list.append(&row);
+        }
+        list.set_visible(!subscriptions.is_empty());
+        imp.subscriptions_empty
+            .set_visible(subscriptions.is_empty());
+
+        let list = &imp.playlists_list;
+        while let Some(row) = list.row_at_index(0) {
+            list.remove(&row);
+        }
+        let playlists = model.playlists();
+        for playlist in &playlists {
+            let row = adw::ExpanderRow::builder()
+                .title(&playlist.name)
+                .subtitle(match playlist.videos.len() {
+                    1 => "1 video".to_string(),
+                    count => format!("{} videos", count),
+                })
+                .use_markup(false)
+                .build();
+            row.add_prefix(&gtk::Image::from_icon_name("view-list-symbolic"));
+            let button = gtk::Button::builder()
+                .icon_name("user-trash-symbolic")
+                .tooltip_text("Remove Playlist")
+                .valign(gtk::Align::Center)
+                .action_name("win.remove-playlist")
+                .action_target(&playlist.id.to_variant())
+                .css_classes(["flat"])
+                .build();
+            row.add_suffix(&button);
+            for video in &playlist.videos {
+                let video_row = adw::ActionRow::builder()
+                    .title(&video.title)
+                    .subtitle(&video.author)
+                    .use_markup(false)
+                    .activatable(true)
+                    .build();
+                video_row.set_action_name(Some("win.open-video"));
+                video_row.set_action_target_value(Some(&video.video_id.to_variant()));
+                let button = gtk::Button::builder()
+                    .icon_name("list-remove-symbolic")
+                    .tooltip_text("Remove from Playlist")
+                    .valign(gtk::Align::Center)
+                    .action_name("win.remove-from-playlist")
+                    .action_target(&(playlist.id.as_str(), video.video_id.as_str()).to_variant())
+                    .css_classes(["flat"])
+                    .build();
+                video_row.add_suffix(&button);
+                row.add_row(&video_row);
+            }
+            list.append(&row);
+        }
+        list.set_visible(!playlists.is_empty());
+        imp.playlists_empty.set_visible(playlists.is_empty());
+
+        let list = &imp.watch_history_list;
+        while let Some(row) = list.row_at_index(0) {
+            list.remove(&row);
+        }
+        let history = model.watch_history();
+        for entry in &history {
+            let row = adw::ActionRow::builder()
+                .title(&entry.title)
+                .subtitle(&entry.author)
+                .use_markup(false)
+                .activatable(true)
+                .build();
+            row.add_prefix(&gtk::Image::from_icon_name("document-open-recent-symbolic"));
+            row.set_action_name(Some("win.open-video"));
+            row.set_action_target_value(Some(&entry.video_id.to_variant()));
+            list.append(&row);
+        }
+        list.set_visible(!history.is_empty());
+        imp.clear_watch_history_button
+            .set_sensitive(!history.is_empty());
+        imp.watch_history_empty.set_visible(history.is_empty());
+    }
+
     async fn build_search(&self, query: &str) {
-        let grid = &self.imp().search_grid;
+        let imp = self.imp();
+        let grid = &imp.search_grid;
+        imp.last_search.replace(query.to_string());
+        imp.suggestions_popover.get().unwrap().popdown();
         if query.is_empty() {
-            grid.set_refreshable(false);
             grid.set_content([].as_slice());
-            grid.set_state(ResultPageState::Message((
-                "system-search-symbolic".to_string(),
-                "Search for something".to_string(),
-                "If you can't find what you're looking for, simplify your search a little bit."
-                    .to_string(),
-            )));
+            self.show_search_history();
             return;
         }
-        grid.set_refreshable(true);
+        imp.search_stack.set_visible_child_name("results");
+        self.model().record_search(query);
 
         let invidious = self.model().invidious();
         let instance = invidious.get_instance();
@@ -330,16 +1562,76 @@ impl PryvidWindow {
         });
     }
 
-    async fn build_trending(&self) {
+    fn setup_trending(&self) {
+        let stack = &self.imp().trending_stack;
+        for category in TrendingCategory::ALL {
+            let grid = ContentGrid::new();
+            grid.set_model(self.model());
+            grid.set_refreshable(true);
+            grid.connect_closure(
+                "refresh",
+                false,
+                closure_local!(@watch self as win => move |_: ContentGrid| {
+                    win.load_trending(category);
+                }),
+            );
+            stack.add_titled(&grid, Some(category.id()), category.title());
+        }
+        stack.connect_visible_child_name_notify(clone!(@weak self as win => move |_| {
+            if let Some(category) = win.visible_trending_category() {
+                if !win.imp().trending_requested.borrow().contains(&category) {
+                    win.load_trending(category);
+                }
+            }
+        }));
+        self.model().settings().connect_changed(
+            Some("trending-region"),
+            clone!(@weak self as win => move |_, _| win.reload_trending()),
+        );
+    }
+
+    fn visible_trending_category(&self) -> Option<TrendingCategory> {
+        self.imp()
+            .trending_stack
+            .visible_child_name()
+            .and_then(|name| TrendingCategory::from_id(&name))
+    }
+
+    /// Forgets every loaded category and loads the visible one again
+    fn reload_trending(&self) {
+        self.imp().trending_requested.borrow_mut().clear();
+        self.load_trending(
+            self.visible_trending_category()
+                .unwrap_or(TrendingCategory::Default),
+        );
+    }
+
+    fn load_trending(&self, category: TrendingCategory) {
+        self.imp().trending_requested.borrow_mut().insert(category);
+        MainContext::default().spawn_local(clone!(@weak self as win => async move {
+            win.build_trending(category).await;
+        }));
+    }
+
+    async fn build_trending(&self, category: TrendingCategory) {
         let invidious = self.model().invidious();
-        let grid = &self.imp().trending_grid;
+        let Some(grid) = self
+            .imp()
+            .trending_stack
+            .child_by_name(category.id())
+            .and_downcast::<ContentGrid>()
+        else {
+            return;
+        };
+        let region = self.model().settings().string("trending-region");
+        let region = Some(region.as_str()).filter(|x| !x.is_empty());
 
         grid.set_state(ResultPageState::Loading);
         grid.set_state(if let Ok(instance) = invidious.get_trending_instance() {
             self.imp()
                 .trending_instance_indicator
                 .set_uri(instance.uri.clone());
-            match instance.trending().await {
+            match instance.trending(category, region).await {
                 Ok(content) => {
                     grid.set_content(content.as_slice());
                     if content.is_empty() {
@@ -361,7 +1653,8 @@ impl PryvidWindow {
 
     fn fetch_startup(&self) {
         MainContext::default().spawn_local(clone!(@weak self as window => async move {
-            futures::join!(window.build_popular(), window.build_trending());
+            window.build_popular().await;
         }));
+        self.reload_trending();
     }
 }